@@ -1,23 +1,50 @@
-use serde::Deserialize;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
 
-use crate::project::find_project_root;
+use crate::export::load_config;
+use crate::project::{find_project_root, PROJECT_CONFIG_MISSING};
 
-#[derive(Debug, Deserialize, Clone, Copy)]
+pub const CREDENTIAL_STORE_LOCKED: &str = "credential_store_locked";
+const DEFAULT_KEYRING_TIMEOUT_SECS: u64 = 15;
+const BUNDLE_HEADER: &str = "ernest-credentials-v1";
+const BUNDLE_SALT_LEN: usize = 16;
+const BUNDLE_NONCE_LEN: usize = 12;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
 #[serde(rename_all = "lowercase")]
 pub enum CredentialTarget {
     Ftp,
     Netlify,
     Vercel,
     Git,
+    Webhook,
+    S3,
+    #[serde(rename = "cloudflare_pages")]
+    CloudflarePages,
+    WebDav,
 }
 
-#[derive(Debug, Deserialize, Clone, Copy)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
 #[serde(rename_all = "lowercase")]
 pub enum CredentialKind {
     Password,
     Token,
+    Passphrase,
+    #[serde(rename = "access_key")]
+    AccessKey,
+    #[serde(rename = "secret_key")]
+    SecretKey,
+    Username,
 }
 
 #[derive(Debug, Deserialize)]
@@ -30,6 +57,14 @@ pub struct CredentialRequest {
     pub kind: CredentialKind,
 }
 
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CredentialStatus {
+    Present,
+    Missing,
+    Unknown,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CredentialSetRequest {
@@ -41,6 +76,53 @@ pub struct CredentialSetRequest {
     pub value: String,
 }
 
+#[derive(Debug, Serialize)]
+pub struct StoredCredential {
+    pub target: CredentialTarget,
+    pub kind: CredentialKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    pub present: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CredentialProjectRequest {
+    pub file_path: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CredentialBundleRequest {
+    pub file_path: String,
+    pub bundle_path: String,
+    pub passphrase: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BundledCredential {
+    target: CredentialTarget,
+    kind: CredentialKind,
+    #[serde(default)]
+    profile: Option<String>,
+    value: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CredentialMigrateRequest {
+    pub old_root: String,
+    pub new_root: String,
+}
+
+// Secrets already stored under the new root are left alone and listed in
+// `conflicts`; their old copies stay where they were.
+#[derive(Debug, Serialize)]
+pub struct CredentialMigration {
+    pub moved: usize,
+    pub conflicts: Vec<StoredCredential>,
+}
+
 #[tauri::command]
 pub fn get_credential(request: CredentialRequest) -> Result<Option<String>, String> {
     lookup_credential(
@@ -51,6 +133,40 @@ pub fn get_credential(request: CredentialRequest) -> Result<Option<String>, Stri
     )
 }
 
+// Reading the Secret Service keyring on Linux can pop an unlock dialog, so the
+// status there only reflects what this session has already seen. Other
+// platforms answer without prompting and are queried directly.
+#[tauri::command]
+pub fn credential_status(request: CredentialRequest) -> Result<CredentialStatus, String> {
+    let project_root = resolve_project_root(&request.file_path)?;
+    let key = credential_key(
+        &project_root,
+        request.target,
+        request.profile.as_deref(),
+        request.kind,
+    );
+    if let Some(present) = known_status(&key) {
+        return Ok(if present {
+            CredentialStatus::Present
+        } else {
+            CredentialStatus::Missing
+        });
+    }
+    if cfg!(target_os = "linux") {
+        return Ok(CredentialStatus::Unknown);
+    }
+    match lookup_credential(
+        &request.file_path,
+        request.target,
+        request.profile.as_deref(),
+        request.kind,
+    ) {
+        Ok(Some(_)) => Ok(CredentialStatus::Present),
+        Ok(None) => Ok(CredentialStatus::Missing),
+        Err(_) => Ok(CredentialStatus::Unknown),
+    }
+}
+
 #[tauri::command]
 pub fn set_credential(request: CredentialSetRequest) -> Result<(), String> {
     if request.value.trim().is_empty() {
@@ -66,7 +182,17 @@ pub fn set_credential(request: CredentialSetRequest) -> Result<(), String> {
     )?;
     entry
         .set_password(request.value.trim())
-        .map_err(|error| error.to_string())
+        .map_err(|error| error.to_string())?;
+    remember_status(
+        credential_key(
+            &project_root,
+            request.target,
+            request.profile.as_deref(),
+            request.kind,
+        ),
+        true,
+    );
+    Ok(())
 }
 
 #[tauri::command]
@@ -79,12 +205,220 @@ pub fn delete_credential(request: CredentialRequest) -> Result<(), String> {
         request.kind,
     )?;
     match entry.delete_password() {
-        Ok(()) => Ok(()),
-        Err(keyring::Error::NoEntry) => Ok(()),
+        Ok(()) | Err(keyring::Error::NoEntry) => {
+            remember_status(
+                credential_key(
+                    &project_root,
+                    request.target,
+                    request.profile.as_deref(),
+                    request.kind,
+                ),
+                false,
+            );
+            Ok(())
+        }
         Err(error) => Err(error.to_string()),
     }
 }
 
+#[tauri::command]
+pub fn list_credentials(
+    request: CredentialProjectRequest,
+) -> Result<Vec<StoredCredential>, String> {
+    let project_root = resolve_project_root(&request.file_path)?;
+    let mut credentials = Vec::new();
+    for (target, profile, kind) in credential_slots(&project_root) {
+        let entry = credential_entry(&project_root, target, profile.as_deref(), kind)?;
+        // A locked keyring would time out on every probe, so stop at the first.
+        let present = read_entry(entry)?.is_some();
+        remember_status(
+            credential_key(&project_root, target, profile.as_deref(), kind),
+            present,
+        );
+        credentials.push(StoredCredential {
+            target,
+            kind,
+            profile,
+            present,
+        });
+    }
+    Ok(credentials)
+}
+
+#[tauri::command]
+pub fn clear_project_credentials(request: CredentialProjectRequest) -> Result<usize, String> {
+    let project_root = resolve_project_root(&request.file_path)?;
+    let mut removed = 0;
+    for (target, profile, kind) in credential_slots(&project_root) {
+        let profile = profile.as_deref();
+        match credential_entry(&project_root, target, profile, kind)?.delete_password() {
+            Ok(()) => removed += 1,
+            Err(keyring::Error::NoEntry) => {}
+            Err(error) => return Err(error.to_string()),
+        }
+        remember_status(credential_key(&project_root, target, profile, kind), false);
+    }
+    Ok(removed)
+}
+
+#[tauri::command]
+pub fn export_credentials(request: CredentialBundleRequest) -> Result<usize, String> {
+    if request.passphrase.is_empty() {
+        return Err("Passphrase is empty".to_string());
+    }
+    let project_root = resolve_project_root(&request.file_path)?;
+    let mut bundled = Vec::new();
+    for (target, profile, kind) in credential_slots(&project_root) {
+        let entry = credential_entry(&project_root, target, profile.as_deref(), kind)?;
+        if let Some(value) = read_entry(entry)? {
+            bundled.push(BundledCredential {
+                target,
+                kind,
+                profile,
+                value,
+            });
+        }
+    }
+    let plaintext = serde_json::to_vec(&bundled).map_err(|error| error.to_string())?;
+    let bundle = seal_bundle(&plaintext, &request.passphrase)?;
+    std::fs::write(&request.bundle_path, bundle).map_err(|error| error.to_string())?;
+    Ok(bundled.len())
+}
+
+#[tauri::command]
+pub fn import_credentials(request: CredentialBundleRequest) -> Result<usize, String> {
+    let project_root = resolve_project_root(&request.file_path)?;
+    let bundle =
+        std::fs::read_to_string(&request.bundle_path).map_err(|error| error.to_string())?;
+    let plaintext = open_bundle(&bundle, &request.passphrase)?;
+    let bundled: Vec<BundledCredential> =
+        serde_json::from_slice(&plaintext).map_err(|error| error.to_string())?;
+    for credential in &bundled {
+        let profile = credential.profile.as_deref();
+        credential_entry(&project_root, credential.target, profile, credential.kind)?
+            .set_password(&credential.value)
+            .map_err(|error| error.to_string())?;
+        remember_status(
+            credential_key(&project_root, credential.target, profile, credential.kind),
+            true,
+        );
+    }
+    Ok(bundled.len())
+}
+
+// Bundle layout: a header line, then base64 of salt, nonce and the AES-256-GCM
+// ciphertext. The key is derived from the passphrase with Argon2id.
+fn seal_bundle(plaintext: &[u8], passphrase: &str) -> Result<String, String> {
+    let mut salt = [0u8; BUNDLE_SALT_LEN];
+    aes_gcm::aead::rand_core::RngCore::fill_bytes(&mut OsRng, &mut salt);
+    let cipher = bundle_cipher(passphrase, &salt)?;
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| "Unable to encrypt credentials".to_string())?;
+
+    let mut payload = salt.to_vec();
+    payload.extend_from_slice(&nonce);
+    payload.extend(ciphertext);
+    Ok(format!("{}\n{}\n", BUNDLE_HEADER, BASE64.encode(payload)))
+}
+
+fn open_bundle(bundle: &str, passphrase: &str) -> Result<Vec<u8>, String> {
+    let mut lines = bundle.lines();
+    if lines.next().map(str::trim) != Some(BUNDLE_HEADER) {
+        return Err("Not an Ernest credentials bundle".to_string());
+    }
+    let payload = BASE64
+        .decode(lines.next().unwrap_or_default().trim())
+        .map_err(|error| error.to_string())?;
+    if payload.len() < BUNDLE_SALT_LEN + BUNDLE_NONCE_LEN {
+        return Err("Credentials bundle is truncated".to_string());
+    }
+    let (salt, rest) = payload.split_at(BUNDLE_SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(BUNDLE_NONCE_LEN);
+    bundle_cipher(passphrase, salt)?
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "Wrong passphrase or damaged credentials bundle".to_string())
+}
+
+fn bundle_cipher(passphrase: &str, salt: &[u8]) -> Result<Aes256Gcm, String> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|error| error.to_string())?;
+    Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)))
+}
+
+// The keyring cannot be enumerated, so every slot known from either root's
+// config is tried.
+#[tauri::command]
+pub fn migrate_credentials(
+    request: CredentialMigrateRequest,
+) -> Result<CredentialMigration, String> {
+    let old_root = canonical_root(Path::new(request.old_root.trim()));
+    let new_root = canonical_root(Path::new(request.new_root.trim()));
+    let mut migration = CredentialMigration {
+        moved: 0,
+        conflicts: Vec::new(),
+    };
+    if old_root == new_root {
+        return Ok(migration);
+    }
+
+    for (target, profile, kind) in migration_slots(&old_root, &new_root) {
+        let profile = profile.as_deref();
+        let old_entry = credential_entry(&old_root, target, profile, kind)?;
+        let value = match read_entry(old_entry)? {
+            Some(value) => value,
+            None => continue,
+        };
+        if read_entry(credential_entry(&new_root, target, profile, kind)?)?.is_some() {
+            migration.conflicts.push(StoredCredential {
+                target,
+                kind,
+                profile: profile.map(str::to_string),
+                present: true,
+            });
+            continue;
+        }
+        credential_entry(&new_root, target, profile, kind)?
+            .set_password(&value)
+            .map_err(|error| error.to_string())?;
+        remember_status(credential_key(&new_root, target, profile, kind), true);
+
+        match credential_entry(&old_root, target, profile, kind)?.delete_password() {
+            Ok(()) | Err(keyring::Error::NoEntry) => {}
+            Err(error) => return Err(error.to_string()),
+        }
+        remember_status(credential_key(&old_root, target, profile, kind), false);
+        migration.moved += 1;
+    }
+    Ok(migration)
+}
+
+// Keys hash the root, so a project opened through a symlink must resolve to
+// the same root. The old root is usually gone after a move, so it is used as
+// given when it cannot be resolved.
+fn canonical_root(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+// The slots of both roots, without duplicates: a profile may only be named in
+// the old config if the new one has not been updated yet.
+fn migration_slots(
+    old_root: &Path,
+    new_root: &Path,
+) -> Vec<(CredentialTarget, Option<String>, CredentialKind)> {
+    let mut seen = HashSet::new();
+    credential_slots(old_root)
+        .into_iter()
+        .chain(credential_slots(new_root))
+        .filter(|(target, profile, kind)| {
+            seen.insert(credential_key(new_root, *target, profile.as_deref(), *kind))
+        })
+        .collect()
+}
+
 pub fn lookup_credential(
     file_path: &str,
     target: CredentialTarget,
@@ -92,17 +426,80 @@ pub fn lookup_credential(
     kind: CredentialKind,
 ) -> Result<Option<String>, String> {
     let project_root = resolve_project_root(file_path)?;
+    let key = credential_key(&project_root, target, profile, kind);
     let entry = credential_entry(&project_root, target, profile, kind)?;
-    match entry.get_password() {
-        Ok(value) => Ok(Some(value)),
-        Err(keyring::Error::NoEntry) => Ok(None),
-        Err(error) => Err(error.to_string()),
+    let value = read_entry(entry)?;
+    remember_status(key, value.is_some());
+    Ok(value)
+}
+
+// Some keyrings block on a GUI unlock prompt; give up instead of wedging
+// the caller. The reader thread is left to finish on its own.
+fn read_entry(entry: keyring::Entry) -> Result<Option<String>, String> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = sender.send(entry.get_password());
+    });
+    match receiver.recv_timeout(keyring_timeout()) {
+        Ok(Ok(value)) => Ok(Some(value)),
+        Ok(Err(keyring::Error::NoEntry)) => Ok(None),
+        Ok(Err(error)) => Err(error.to_string()),
+        Err(_) => Err(CREDENTIAL_STORE_LOCKED.to_string()),
     }
 }
 
+fn credential_slots(
+    project_root: &Path,
+) -> Vec<(CredentialTarget, Option<String>, CredentialKind)> {
+    let config = load_config(project_root).ok();
+    let mut slots = Vec::new();
+    for target in CredentialTarget::ALL {
+        let mut profiles = vec![None];
+        if let Some(config) = &config {
+            profiles.extend(config.credential_profiles(target).into_iter().map(Some));
+        }
+        for profile in profiles {
+            for kind in CredentialKind::ALL {
+                slots.push((target, profile.clone(), kind));
+            }
+        }
+    }
+    slots
+}
+
+fn status_cache() -> &'static Mutex<HashMap<String, bool>> {
+    static CACHE: OnceLock<Mutex<HashMap<String, bool>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn known_status(key: &str) -> Option<bool> {
+    let cache = status_cache()
+        .lock()
+        .expect("credential cache lock poisoned");
+    cache.get(key).copied()
+}
+
+fn remember_status(key: String, present: bool) {
+    let mut cache = status_cache()
+        .lock()
+        .expect("credential cache lock poisoned");
+    cache.insert(key, present);
+}
+
+fn keyring_timeout() -> Duration {
+    let secs = std::env::var("ERNEST_KEYRING_TIMEOUT_SECS")
+        .ok()
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .filter(|value| *value > 0)
+        .unwrap_or(DEFAULT_KEYRING_TIMEOUT_SECS);
+    Duration::from_secs(secs)
+}
+
 fn resolve_project_root(file_path: &str) -> Result<PathBuf, String> {
     let path = Path::new(file_path);
-    find_project_root(path).ok_or_else(|| "No .export.toml found in parent folders".to_string())
+    find_project_root(path)
+        .map(|root| canonical_root(&root))
+        .ok_or_else(|| PROJECT_CONFIG_MISSING.to_string())
 }
 
 fn credential_entry(
@@ -135,21 +532,123 @@ fn credential_key(
 }
 
 impl CredentialTarget {
+    pub const ALL: [CredentialTarget; 8] = [
+        Self::Ftp,
+        Self::Netlify,
+        Self::Vercel,
+        Self::Git,
+        Self::Webhook,
+        Self::S3,
+        Self::CloudflarePages,
+        Self::WebDav,
+    ];
+
     fn as_str(&self) -> &'static str {
         match self {
             Self::Ftp => "ftp",
             Self::Netlify => "netlify",
             Self::Vercel => "vercel",
             Self::Git => "git",
+            Self::Webhook => "webhook",
+            Self::S3 => "s3",
+            Self::CloudflarePages => "cloudflare_pages",
+            Self::WebDav => "webdav",
         }
     }
 }
 
 impl CredentialKind {
-    fn as_str(&self) -> &'static str {
+    pub const ALL: [CredentialKind; 6] = [
+        Self::Password,
+        Self::Token,
+        Self::Passphrase,
+        Self::AccessKey,
+        Self::SecretKey,
+        Self::Username,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
         match self {
             Self::Password => "password",
             Self::Token => "token",
+            Self::Passphrase => "passphrase",
+            Self::AccessKey => "access_key",
+            Self::SecretKey => "secret_key",
+            Self::Username => "username",
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn credential_bundle_round_trips_only_with_the_passphrase() {
+        let bundle = seal_bundle(b"[\"secret\"]", "correct horse").unwrap();
+        assert!(bundle.starts_with(BUNDLE_HEADER));
+        assert!(!bundle.contains("secret"));
+
+        assert_eq!(
+            open_bundle(&bundle, "correct horse").unwrap(),
+            b"[\"secret\"]"
+        );
+        assert_eq!(
+            open_bundle(&bundle, "wrong horse").unwrap_err(),
+            "Wrong passphrase or damaged credentials bundle"
+        );
+        assert!(open_bundle("not a bundle", "correct horse").is_err());
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn migration_slots_cover_profiles_from_both_roots() {
+        let base = std::env::temp_dir().join(format!(
+            "ernest-test-migrate-{}",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        let (old_root, new_root) = (base.join("old"), base.join("new"));
+        std::fs::create_dir_all(&old_root).unwrap();
+        std::fs::create_dir_all(&new_root).unwrap();
+        let config = |profile: &str| {
+            format!(
+                "version = 1\n[netlify]\nenabled = true\nsite_id = \"a\"\n\
+                 [netlify.profiles.{}]\nenabled = true\n",
+                profile
+            )
+        };
+        std::fs::write(old_root.join(".export.toml"), config("staging")).unwrap();
+        std::fs::write(new_root.join(".export.toml"), config("prod")).unwrap();
+
+        let slots = migration_slots(&old_root, &new_root);
+        let netlify_profiles: HashSet<Option<String>> = slots
+            .iter()
+            .filter(|(target, _, kind)| {
+                matches!(target, CredentialTarget::Netlify) && matches!(kind, CredentialKind::Token)
+            })
+            .map(|(_, profile, _)| profile.clone())
+            .collect();
+        assert_eq!(
+            netlify_profiles,
+            HashSet::from([None, Some("staging".to_string()), Some("prod".to_string())])
+        );
+        let defaults = slots.iter().filter(|(_, profile, _)| profile.is_none());
+        assert_eq!(
+            defaults.count(),
+            CredentialTarget::ALL.len() * CredentialKind::ALL.len()
+        );
+
+        let linked = base.join("link");
+        std::os::unix::fs::symlink(&new_root, &linked).unwrap();
+        assert_eq!(canonical_root(&linked), new_root.canonicalize().unwrap());
+        assert_eq!(
+            resolve_project_root(&linked.join("post.md").to_string_lossy()).unwrap(),
+            new_root.canonicalize().unwrap()
+        );
+
+        let _ = std::fs::remove_dir_all(&base);
+    }
+}
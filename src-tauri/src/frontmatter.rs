@@ -0,0 +1,549 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::publish::{backup_dir_for, publish_output_dir};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrontmatterFormat {
+    Yaml,
+    Toml,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FrontmatterDefaultsRequest {
+    pub project_root: String,
+    pub defaults: BTreeMap<String, String>,
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FrontmatterDefaultsResult {
+    pub file_path: String,
+    pub keys: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct Frontmatter<'a> {
+    pub format: FrontmatterFormat,
+    pub raw: &'a str,
+    pub body: &'a str,
+}
+
+pub fn strip_bom(content: &str) -> &str {
+    content.strip_prefix('\u{feff}').unwrap_or(content)
+}
+
+pub fn split_frontmatter(content: &str) -> Option<Frontmatter<'_>> {
+    let content = strip_bom(content);
+    let (fence, format) = if content.starts_with("---") {
+        ("---", FrontmatterFormat::Yaml)
+    } else if content.starts_with("+++") {
+        ("+++", FrontmatterFormat::Toml)
+    } else {
+        return None;
+    };
+
+    let first_line_end = content.find('\n')?;
+    if content[..first_line_end].trim_end() != fence {
+        return None;
+    }
+    let start = first_line_end + 1;
+
+    let mut offset = start;
+    for line in content[start..].split_inclusive('\n') {
+        if line.trim_end() == fence {
+            return Some(Frontmatter {
+                format,
+                raw: &content[start..offset],
+                body: &content[offset + line.len()..],
+            });
+        }
+        offset += line.len();
+    }
+    None
+}
+
+#[tauri::command]
+pub fn apply_frontmatter_defaults(
+    request: FrontmatterDefaultsRequest,
+) -> Result<Vec<FrontmatterDefaultsResult>, String> {
+    let project_root = PathBuf::from(&request.project_root);
+    if !project_root.is_dir() {
+        return Err("Project root is missing".to_string());
+    }
+    let defaults: Vec<(String, String)> = request
+        .defaults
+        .into_iter()
+        .map(|(key, value)| (key.trim().to_string(), value))
+        .filter(|(key, _)| !key.is_empty())
+        .collect();
+    if defaults.is_empty() {
+        return Err("No frontmatter defaults provided".to_string());
+    }
+
+    let project_root = project_root.canonicalize().unwrap_or(project_root);
+    let output_dir = publish_output_dir(&project_root)?;
+    let skip = [backup_dir_for(&output_dir)?, output_dir];
+    let mut files = Vec::new();
+    collect_markdown_files(&project_root, &skip, &mut files)?;
+    files.sort();
+
+    let mut results = Vec::new();
+    for file in files {
+        let content = fs::read_to_string(&file).map_err(|error| error.to_string())?;
+        let (updated, keys) = apply_defaults(&content, &defaults, request.overwrite);
+        if keys.is_empty() {
+            continue;
+        }
+        fs::write(&file, updated).map_err(|error| error.to_string())?;
+        results.push(FrontmatterDefaultsResult {
+            file_path: file.display().to_string(),
+            keys,
+        });
+    }
+    Ok(results)
+}
+
+// Hidden folders (.git, drafts) and the publish output in `skip` are not
+// source documents. Symlinks are left alone: a linked folder can loop back on
+// itself, and a linked file may live outside the project.
+fn collect_markdown_files(
+    dir: &Path,
+    skip: &[PathBuf],
+    files: &mut Vec<PathBuf>,
+) -> Result<(), String> {
+    for entry in fs::read_dir(dir).map_err(|error| error.to_string())? {
+        let entry = entry.map_err(|error| error.to_string())?;
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') || name == "node_modules" || skip.contains(&path) {
+            continue;
+        }
+        let file_type = entry.file_type().map_err(|error| error.to_string())?;
+        if file_type.is_dir() {
+            collect_markdown_files(&path, skip, files)?;
+        } else if file_type.is_file()
+            && path
+                .extension()
+                .map(|ext| ext.eq_ignore_ascii_case("md"))
+                .unwrap_or(false)
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+pub fn apply_defaults(
+    content: &str,
+    defaults: &[(String, String)],
+    overwrite: bool,
+) -> (String, Vec<String>) {
+    let frontmatter = match split_frontmatter(content) {
+        Some(frontmatter) => frontmatter,
+        None => {
+            let raw: String = defaults
+                .iter()
+                .map(|(key, value)| format!("{}: {}\n", key, yaml_scalar(value)))
+                .collect();
+            let keys = defaults.iter().map(|(key, _)| key.clone()).collect();
+            return (format!("---\n{}---\n{}", raw, strip_bom(content)), keys);
+        }
+    };
+
+    let mut keys = Vec::new();
+    let (fence, raw) = match frontmatter.format {
+        FrontmatterFormat::Toml => {
+            let mut table: toml::Table = match toml::from_str(frontmatter.raw) {
+                Ok(table) => table,
+                Err(_) => return (content.to_string(), keys),
+            };
+            for (key, value) in defaults {
+                if overwrite || !table.contains_key(key) {
+                    table.insert(key.clone(), toml::Value::String(value.clone()));
+                    keys.push(key.clone());
+                }
+            }
+            if keys.is_empty() {
+                return (content.to_string(), keys);
+            }
+            match toml::to_string(&table) {
+                Ok(raw) => ("+++", raw),
+                Err(_) => return (content.to_string(), Vec::new()),
+            }
+        }
+        FrontmatterFormat::Yaml => {
+            let mut lines: Vec<String> = frontmatter
+                .raw
+                .lines()
+                .map(|line| line.to_string())
+                .collect();
+            for (key, value) in defaults {
+                let line = format!("{}: {}", key, yaml_scalar(value));
+                let existing = lines.iter().position(|candidate| {
+                    candidate
+                        .split_once(':')
+                        .map(|(name, _)| name == key)
+                        .unwrap_or(false)
+                });
+                match existing {
+                    None => lines.push(line),
+                    Some(index)
+                        if overwrite
+                            && lines
+                                .get(index + 1)
+                                .map(|next| !next.starts_with(' ') && !next.starts_with('-'))
+                                .unwrap_or(true) =>
+                    {
+                        lines[index] = line
+                    }
+                    Some(_) => continue,
+                }
+                keys.push(key.clone());
+            }
+            if keys.is_empty() {
+                return (content.to_string(), keys);
+            }
+            let mut raw = lines.join("\n");
+            raw.push('\n');
+            ("---", raw)
+        }
+    };
+    (
+        format!("{}\n{}{}\n{}", fence, raw, fence, frontmatter.body),
+        keys,
+    )
+}
+
+fn yaml_scalar(value: &str) -> String {
+    let needs_quotes = value.is_empty()
+        || value.trim() != value
+        || value.contains(": ")
+        || value.contains(" #")
+        || value.starts_with(|c: char| "-?:,[]{}#&*!|>'\"%@`".contains(c));
+    if needs_quotes {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn scalar_value(frontmatter: &Frontmatter, key: &str) -> Option<String> {
+    match frontmatter.format {
+        FrontmatterFormat::Toml => {
+            let table: toml::Table = toml::from_str(frontmatter.raw).ok()?;
+            table.get(key)?.as_str().map(|value| value.to_string())
+        }
+        FrontmatterFormat::Yaml => frontmatter.raw.lines().find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            if name != key {
+                return None;
+            }
+            let value = unquote(value.trim());
+            if value.is_empty() {
+                None
+            } else {
+                Some(value.to_string())
+            }
+        }),
+    }
+}
+
+pub fn string_values(frontmatter: &Frontmatter, key: &str) -> Vec<String> {
+    match frontmatter.format {
+        FrontmatterFormat::Toml => {
+            let table: toml::Table = match toml::from_str(frontmatter.raw) {
+                Ok(table) => table,
+                Err(_) => return Vec::new(),
+            };
+            match table.get(key) {
+                Some(toml::Value::String(value)) => vec![value.clone()],
+                Some(toml::Value::Array(values)) => values
+                    .iter()
+                    .filter_map(|value| value.as_str().map(|value| value.to_string()))
+                    .collect(),
+                _ => Vec::new(),
+            }
+        }
+        FrontmatterFormat::Yaml => {
+            let mut lines = frontmatter.raw.lines();
+            let value = match lines.find_map(|line| {
+                let (name, value) = line.split_once(':')?;
+                (name == key).then(|| unquote(value.trim()))
+            }) {
+                Some(value) => value,
+                None => return Vec::new(),
+            };
+            let values: Vec<&str> = if value.is_empty() {
+                lines
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map_while(|line| line.strip_prefix("- ").or((line == "-").then_some("")))
+                    .map(unquote)
+                    .collect()
+            } else if let Some(list) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+                list.split(',').map(unquote).collect()
+            } else {
+                vec![value]
+            };
+            values
+                .into_iter()
+                .filter(|value| !value.is_empty())
+                .map(|value| value.to_string())
+                .collect()
+        }
+    }
+}
+
+pub fn drop_keys(content: &str, keys: &[String]) -> (String, Vec<String>) {
+    let frontmatter = match split_frontmatter(content) {
+        Some(frontmatter) => frontmatter,
+        None => return (content.to_string(), Vec::new()),
+    };
+    let mut raw = frontmatter.raw.to_string();
+    let mut removed = Vec::new();
+
+    match frontmatter.format {
+        FrontmatterFormat::Toml => {
+            let mut table: toml::Table = match toml::from_str(frontmatter.raw) {
+                Ok(table) => table,
+                Err(_) => return (content.to_string(), Vec::new()),
+            };
+            for key in keys {
+                let path: Vec<&str> = key.split('.').collect();
+                if remove_toml_path(&mut table, &path) {
+                    removed.push(key.clone());
+                }
+            }
+            if !removed.is_empty() {
+                raw = match toml::to_string(&table) {
+                    Ok(raw) => raw,
+                    Err(_) => return (content.to_string(), Vec::new()),
+                };
+            }
+        }
+        FrontmatterFormat::Yaml => {
+            for key in keys {
+                let path: Vec<&str> = key.split('.').collect();
+                let lines: Vec<&str> = raw.split_inclusive('\n').collect();
+                if let Some((start, end)) = find_yaml_block(&lines, 0, lines.len(), &path) {
+                    let mut next = lines[..start].concat();
+                    next.push_str(&lines[end..].concat());
+                    raw = next;
+                    removed.push(key.clone());
+                }
+            }
+        }
+    }
+
+    if removed.is_empty() {
+        return (content.to_string(), removed);
+    }
+    let fence = match frontmatter.format {
+        FrontmatterFormat::Yaml => "---",
+        FrontmatterFormat::Toml => "+++",
+    };
+    if !raw.is_empty() && !raw.ends_with('\n') {
+        raw.push('\n');
+    }
+    (
+        format!("{}\n{}{}\n{}", fence, raw, fence, frontmatter.body),
+        removed,
+    )
+}
+
+fn remove_toml_path(table: &mut toml::Table, path: &[&str]) -> bool {
+    match path {
+        [] => false,
+        [key] => table.remove(*key).is_some(),
+        [key, rest @ ..] => match table.get_mut(*key).and_then(|value| value.as_table_mut()) {
+            Some(child) => remove_toml_path(child, rest),
+            None => false,
+        },
+    }
+}
+
+fn find_yaml_block(
+    lines: &[&str],
+    from: usize,
+    to: usize,
+    path: &[&str],
+) -> Option<(usize, usize)> {
+    let (key, rest) = path.split_first()?;
+    let indent = lines[from..to]
+        .iter()
+        .find(|line| is_yaml_content(line))
+        .map(|line| indent_of(line))?;
+
+    for index in from..to {
+        let line = lines[index];
+        if !is_yaml_content(line) || indent_of(line) != indent {
+            continue;
+        }
+        let name = match line.trim().split_once(':') {
+            Some((name, _)) => unquote(name.trim()),
+            None => continue,
+        };
+        if name != *key {
+            continue;
+        }
+        let end = (index + 1..to)
+            .find(|next| is_yaml_content(lines[*next]) && indent_of(lines[*next]) <= indent)
+            .unwrap_or(to);
+        if rest.is_empty() {
+            return Some((index, end));
+        }
+        return find_yaml_block(lines, index + 1, end, rest);
+    }
+    None
+}
+
+fn is_yaml_content(line: &str) -> bool {
+    let trimmed = line.trim();
+    !trimmed.is_empty() && !trimmed.starts_with('#')
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+fn unquote(value: &str) -> &str {
+    let value = match value.find(" #") {
+        Some(index) if !value.starts_with('"') && !value.starts_with('\'') => &value[..index],
+        _ => value,
+    };
+    let value = value.trim();
+    for quote in ['"', '\''] {
+        if value.len() >= 2 && value.starts_with(quote) && value.ends_with(quote) {
+            return &value[1..value.len() - 1];
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_frontmatter_reads_yaml_scalars() {
+        let content = "---\ntitle: \"Hello\"\npermalink: /blog/hello/ # pretty\n---\nBody\n";
+        let frontmatter = split_frontmatter(content).unwrap();
+        assert_eq!(frontmatter.format, FrontmatterFormat::Yaml);
+        assert_eq!(frontmatter.body, "Body\n");
+        assert_eq!(
+            scalar_value(&frontmatter, "title").as_deref(),
+            Some("Hello")
+        );
+        assert_eq!(
+            scalar_value(&frontmatter, "permalink").as_deref(),
+            Some("/blog/hello/")
+        );
+    }
+
+    #[test]
+    fn split_frontmatter_reads_toml_scalars() {
+        let content = "+++\ntitle = \"Hello\"\n+++\nBody";
+        let frontmatter = split_frontmatter(content).unwrap();
+        assert_eq!(frontmatter.format, FrontmatterFormat::Toml);
+        assert_eq!(
+            scalar_value(&frontmatter, "title").as_deref(),
+            Some("Hello")
+        );
+        assert!(split_frontmatter("No frontmatter").is_none());
+    }
+
+    #[test]
+    fn string_values_reads_scalars_and_lists() {
+        let content = "---\ncover: img/cover.png\ntags: [a, \"b\"]\ngallery:\n  - one.png\n  - 'two.png'\ntitle: x\n---\n";
+        let frontmatter = split_frontmatter(content).unwrap();
+        assert_eq!(string_values(&frontmatter, "cover"), vec!["img/cover.png"]);
+        assert_eq!(string_values(&frontmatter, "tags"), vec!["a", "b"]);
+        assert_eq!(
+            string_values(&frontmatter, "gallery"),
+            vec!["one.png", "two.png"]
+        );
+        assert!(string_values(&frontmatter, "image").is_empty());
+
+        let content = "+++\nimage = [\"a.png\", \"b.png\"]\n+++\n";
+        let frontmatter = split_frontmatter(content).unwrap();
+        assert_eq!(string_values(&frontmatter, "image"), vec!["a.png", "b.png"]);
+    }
+
+    #[test]
+    fn split_frontmatter_ignores_leading_bom() {
+        let content = "\u{feff}---\ntitle: Hello\n---\nBody";
+        let frontmatter = split_frontmatter(content).unwrap();
+        assert_eq!(
+            scalar_value(&frontmatter, "title").as_deref(),
+            Some("Hello")
+        );
+        assert_eq!(strip_bom(content), &content[3..]);
+    }
+
+    #[test]
+    fn apply_defaults_respects_overwrite() {
+        let defaults = vec![
+            ("layout".to_string(), "post".to_string()),
+            ("author".to_string(), "me".to_string()),
+        ];
+        let (output, keys) = apply_defaults("---\nauthor: you\n---\nBody", &defaults, false);
+        assert_eq!(keys, vec!["layout"]);
+        assert_eq!(output, "---\nauthor: you\nlayout: post\n---\nBody");
+
+        let (output, keys) = apply_defaults("---\nauthor: you\n---\nBody", &defaults, true);
+        assert_eq!(keys, vec!["layout", "author"]);
+        assert_eq!(output, "---\nauthor: me\nlayout: post\n---\nBody");
+
+        let (output, _) = apply_defaults("Body", &defaults, false);
+        assert_eq!(output, "---\nlayout: post\nauthor: me\n---\nBody");
+    }
+
+    #[test]
+    fn drop_keys_removes_nested_yaml_and_toml_keys() {
+        let content = "---\ntitle: Hello\n_internal: true\nmeta:\n  review_notes: |\n    fix intro\n  author: me\n---\nBody\n";
+        let keys = vec![
+            "_internal".to_string(),
+            "meta.review_notes".to_string(),
+            "missing".to_string(),
+        ];
+        let (output, removed) = drop_keys(content, &keys);
+        assert_eq!(removed, vec!["_internal", "meta.review_notes"]);
+        assert_eq!(
+            output,
+            "---\ntitle: Hello\nmeta:\n  author: me\n---\nBody\n"
+        );
+
+        let content = "+++\ntitle = \"Hello\"\n[meta]\nreview_notes = \"x\"\n+++\nBody";
+        let (output, removed) = drop_keys(content, &["meta.review_notes".to_string()]);
+        assert_eq!(removed, vec!["meta.review_notes"]);
+        assert!(!output.contains("review_notes"));
+        assert!(output.ends_with("+++\nBody"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn collect_markdown_files_skips_output_and_symlinks() {
+        let suffix = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!("ernest-test-frontmatter-walk-{}", suffix));
+        fs::create_dir_all(root.join("posts")).unwrap();
+        fs::create_dir_all(root.join("site")).unwrap();
+        fs::write(root.join("posts/a.md"), "A").unwrap();
+        fs::write(root.join("site/a.md"), "A").unwrap();
+        std::os::unix::fs::symlink(&root, root.join("posts/loop")).unwrap();
+
+        let mut files = Vec::new();
+        collect_markdown_files(&root, &[root.join("site")], &mut files).unwrap();
+        assert_eq!(files, vec![root.join("posts/a.md")]);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,899 @@
+use git2::{
+    BranchType, Cred, CredentialType, Delta, ErrorCode, IndexAddOption, Oid, PushOptions,
+    Reference, RemoteCallbacks, Repository, Signature, Status, StatusOptions,
+};
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusEntry {
+    pub code: String,
+    pub path: String,
+}
+
+pub fn open(path: &Path) -> Result<Repository, String> {
+    Repository::discover(path).map_err(|error| error.message().to_string())
+}
+
+pub fn init(path: &Path) -> Result<Repository, String> {
+    Repository::init(path).map_err(|error| error.message().to_string())
+}
+
+pub fn workdir(repo: &Repository) -> Result<PathBuf, String> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| "repository has no working tree".to_string())?;
+    Ok(workdir
+        .canonicalize()
+        .unwrap_or_else(|_| workdir.to_path_buf()))
+}
+
+pub fn status(repo: &Repository) -> Result<Vec<StatusEntry>, String> {
+    let mut options = StatusOptions::new();
+    options
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .include_ignored(false);
+    let statuses = repo
+        .statuses(Some(&mut options))
+        .map_err(|error| error.message().to_string())?;
+    Ok(statuses
+        .iter()
+        .filter_map(|entry| {
+            let path = entry.path()?.to_string();
+            Some(StatusEntry {
+                code: status_code(entry.status()),
+                path,
+            })
+        })
+        .collect())
+}
+
+pub fn format_status(entries: &[StatusEntry]) -> String {
+    entries
+        .iter()
+        .map(|entry| format!("{} {}", entry.code, entry.path))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn status_code(status: Status) -> String {
+    if status.is_conflicted() {
+        return "UU".to_string();
+    }
+    if status.is_wt_new() {
+        return "??".to_string();
+    }
+    let index = if status.is_index_new() {
+        'A'
+    } else if status.is_index_modified() {
+        'M'
+    } else if status.is_index_deleted() {
+        'D'
+    } else if status.is_index_renamed() {
+        'R'
+    } else if status.is_index_typechange() {
+        'T'
+    } else {
+        ' '
+    };
+    let worktree = if status.is_wt_modified() {
+        'M'
+    } else if status.is_wt_deleted() {
+        'D'
+    } else if status.is_wt_renamed() {
+        'R'
+    } else if status.is_wt_typechange() {
+        'T'
+    } else {
+        ' '
+    };
+    format!("{}{}", index, worktree)
+}
+
+pub fn current_branch(repo: &Repository) -> Result<String, String> {
+    match repo.head() {
+        Ok(head) if head.is_branch() => Ok(head.shorthand().unwrap_or("HEAD").to_string()),
+        Ok(_) => Ok("HEAD".to_string()),
+        Err(error) if error.code() == ErrorCode::UnbornBranch => unborn_branch(repo),
+        Err(error) => Err(error.message().to_string()),
+    }
+}
+
+fn unborn_branch(repo: &Repository) -> Result<String, String> {
+    let head = repo
+        .find_reference("HEAD")
+        .map_err(|error| error.message().to_string())?;
+    Ok(head
+        .symbolic_target()
+        .and_then(|target| target.strip_prefix("refs/heads/"))
+        .unwrap_or("HEAD")
+        .to_string())
+}
+
+pub fn branch_exists(repo: &Repository, name: &str) -> bool {
+    repo.find_branch(name, BranchType::Local).is_ok()
+}
+
+pub fn checkout_branch(repo: &Repository, name: &str, create: bool) -> Result<(), String> {
+    let refname = format!("refs/heads/{}", name);
+    if create {
+        let head = repo
+            .head()
+            .and_then(|head| head.peel_to_commit())
+            .map_err(|error| error.message().to_string())?;
+        repo.branch(name, &head, false)
+            .map_err(|error| error.message().to_string())?;
+        return repo
+            .set_head(&refname)
+            .map_err(|error| error.message().to_string());
+    }
+
+    let target = repo
+        .revparse_single(&refname)
+        .map_err(|error| error.message().to_string())?;
+    repo.checkout_tree(&target, Some(git2::build::CheckoutBuilder::new().safe()))
+        .map_err(|error| error.message().to_string())?;
+    repo.set_head(&refname)
+        .map_err(|error| error.message().to_string())
+}
+
+pub fn reset_branch(repo: &Repository, name: &str) -> Result<(), String> {
+    let refname = format!("refs/heads/{}", name);
+    let head = match repo.head() {
+        Ok(head) => head,
+        Err(error) if error.code() == ErrorCode::UnbornBranch => {
+            return repo
+                .set_head(&refname)
+                .map_err(|error| error.message().to_string());
+        }
+        Err(error) => return Err(error.message().to_string()),
+    };
+    if head.is_branch() && head.shorthand() == Some(name) {
+        return Ok(());
+    }
+    let commit = head
+        .peel_to_commit()
+        .map_err(|error| error.message().to_string())?;
+    repo.branch(name, &commit, true)
+        .map_err(|error| error.message().to_string())?;
+    repo.set_head(&refname)
+        .map_err(|error| error.message().to_string())
+}
+
+pub fn branch_tip(repo: &Repository, name: &str) -> Result<Oid, String> {
+    repo.find_reference(&format!("refs/heads/{}", name))
+        .ok()
+        .and_then(|reference| reference.target())
+        .ok_or_else(|| format!("branch '{}' has no commits", name))
+}
+
+pub fn resolve_commit(repo: &Repository, spec: &str) -> Result<Oid, String> {
+    repo.revparse_single(spec)
+        .and_then(|object| object.peel_to_commit())
+        .map(|commit| commit.id())
+        .map_err(|error| error.message().to_string())
+}
+
+pub fn reset_branch_hard(repo: &Repository, name: &str, target: Oid) -> Result<(), String> {
+    let refname = format!("refs/heads/{}", name);
+    let commit = repo
+        .find_commit(target)
+        .map_err(|error| error.message().to_string())?;
+    repo.set_head(&refname)
+        .map_err(|error| error.message().to_string())?;
+    repo.find_reference(&refname)
+        .and_then(|mut reference| reference.set_target(target, "rollback"))
+        .map_err(|error| error.message().to_string())?;
+    repo.reset(commit.as_object(), git2::ResetType::Hard, None)
+        .map_err(|error| error.message().to_string())
+}
+
+pub fn add(repo: &Repository, paths: &[String]) -> Result<(), String> {
+    let root = workdir(repo)?;
+    let mut index = repo.index().map_err(|error| error.message().to_string())?;
+    for path in paths {
+        let relative = relative_path(&root, Path::new(path))?;
+        let result = if root.join(&relative).exists() {
+            index.add_path(&relative)
+        } else {
+            index.remove_path(&relative)
+        };
+        result.map_err(|error| format!("{}: {}", relative.display(), error.message()))?;
+    }
+    index.write().map_err(|error| error.message().to_string())
+}
+
+pub fn add_all(repo: &Repository) -> Result<(), String> {
+    let mut index = repo.index().map_err(|error| error.message().to_string())?;
+    index
+        .add_all(["*"], IndexAddOption::DEFAULT, None)
+        .and_then(|_| index.update_all(["*"], None))
+        .and_then(|_| index.write())
+        .map_err(|error| error.message().to_string())
+}
+
+// What `add_all` would stage in `workdir`, without writing the index. With no
+// repository the changes are read through a scratch one in the temp folder,
+// so nothing is created in `workdir`.
+pub fn pending_changes(
+    workdir: &Path,
+    repo: Option<&Repository>,
+) -> Result<Vec<StatusEntry>, String> {
+    if let Some(repo) = repo {
+        return staged_changes(repo);
+    }
+    let scratch_dir = std::env::temp_dir().join(format!("ernest-scratch-{}", uuid::Uuid::new_v4()));
+    let result = Repository::init_bare(&scratch_dir)
+        .and_then(|scratch| scratch.set_workdir(workdir, false).map(|_| scratch))
+        .map_err(|error| error.message().to_string())
+        .and_then(|scratch| staged_changes(&scratch));
+    let _ = std::fs::remove_dir_all(&scratch_dir);
+    result
+}
+
+fn staged_changes(repo: &Repository) -> Result<Vec<StatusEntry>, String> {
+    let mut index = repo.index().map_err(|error| error.message().to_string())?;
+    index
+        .add_all(["*"], IndexAddOption::DEFAULT, None)
+        .and_then(|_| index.update_all(["*"], None))
+        .map_err(|error| error.message().to_string())?;
+    let head = repo.head().ok().and_then(|head| head.peel_to_tree().ok());
+    let diff = repo
+        .diff_tree_to_index(head.as_ref(), Some(&index), None)
+        .map_err(|error| error.message().to_string())?;
+    Ok(diff
+        .deltas()
+        .filter_map(|delta| {
+            let code = match delta.status() {
+                Delta::Added => "A ",
+                Delta::Modified => "M ",
+                Delta::Deleted => "D ",
+                Delta::Renamed => "R ",
+                Delta::Typechange => "T ",
+                _ => return None,
+            };
+            let path = delta
+                .new_file()
+                .path()
+                .or_else(|| delta.old_file().path())?;
+            Some(StatusEntry {
+                code: code.to_string(),
+                path: path.to_string_lossy().replace('\\', "/"),
+            })
+        })
+        .collect())
+}
+
+fn relative_path(root: &Path, path: &Path) -> Result<PathBuf, String> {
+    if path.is_relative() {
+        return Ok(path.to_path_buf());
+    }
+    if let Ok(relative) = path.strip_prefix(root) {
+        return Ok(relative.to_path_buf());
+    }
+    // The file may be gone already, so canonicalize its parent instead.
+    let parent = path
+        .parent()
+        .and_then(|parent| parent.canonicalize().ok())
+        .unwrap_or_default();
+    let name = path.file_name().unwrap_or_default();
+    parent
+        .join(name)
+        .strip_prefix(root)
+        .map(Path::to_path_buf)
+        .map_err(|_| format!("{} is outside the repository", path.display()))
+}
+
+pub fn commit(
+    repo: &Repository,
+    message: &str,
+    paths: &[String],
+    author_name: Option<&str>,
+    author_email: Option<&str>,
+) -> Result<bool, String> {
+    let parent = match repo.head() {
+        Ok(head) => Some(
+            head.peel_to_commit()
+                .map_err(|error| error.message().to_string())?,
+        ),
+        Err(error) if error.code() == ErrorCode::UnbornBranch => None,
+        Err(error) => return Err(error.message().to_string()),
+    };
+
+    let mut index = repo.index().map_err(|error| error.message().to_string())?;
+    let tree_id = if paths.is_empty() {
+        index.write_tree()
+    } else {
+        partial_tree(repo, &index, parent.as_ref(), paths)
+    }
+    .map_err(|error| error.message().to_string())?;
+
+    let unchanged = match &parent {
+        Some(parent) => parent.tree_id() == tree_id,
+        None => index.is_empty(),
+    };
+    if unchanged {
+        return Ok(false);
+    }
+
+    let tree = repo
+        .find_tree(tree_id)
+        .map_err(|error| error.message().to_string())?;
+    let signature = signature(repo, author_name, author_email)?;
+    let parents: Vec<&git2::Commit> = parent.iter().collect();
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        message,
+        &tree,
+        &parents,
+    )
+    .map_err(|error| error.message().to_string())?;
+    Ok(true)
+}
+
+pub fn signs_commits(repo: &Repository) -> bool {
+    repo.config()
+        .and_then(|config| config.get_bool("commit.gpgsign"))
+        .unwrap_or(false)
+}
+
+pub fn has_commit_hooks(repo: &Repository) -> bool {
+    let hooks = match repo
+        .config()
+        .and_then(|config| config.get_path("core.hooksPath"))
+    {
+        Ok(path) if path.is_relative() => repo.workdir().unwrap_or(repo.path()).join(path),
+        Ok(path) => path,
+        Err(_) => repo.path().join("hooks"),
+    };
+    [
+        "pre-commit",
+        "prepare-commit-msg",
+        "commit-msg",
+        "post-commit",
+    ]
+    .iter()
+    .any(|name| hooks.join(name).is_file())
+}
+
+fn partial_tree(
+    repo: &Repository,
+    index: &git2::Index,
+    parent: Option<&git2::Commit>,
+    paths: &[String],
+) -> Result<Oid, git2::Error> {
+    let root = workdir(repo).map_err(|error| git2::Error::from_str(&error))?;
+    let mut partial = git2::Index::new()?;
+    if let Some(parent) = parent {
+        partial.read_tree(&parent.tree()?)?;
+    }
+    for path in paths {
+        let relative =
+            relative_path(&root, Path::new(path)).map_err(|error| git2::Error::from_str(&error))?;
+        match index.get_path(&relative, 0) {
+            Some(entry) => partial.add(&entry)?,
+            None => {
+                let _ = partial.remove_path(&relative);
+            }
+        }
+    }
+    partial.write_tree_to(repo)
+}
+
+fn signature(
+    repo: &Repository,
+    name: Option<&str>,
+    email: Option<&str>,
+) -> Result<Signature<'static>, String> {
+    let config = repo.config().map_err(|error| error.message().to_string())?;
+    let name = match name {
+        Some(name) => name.trim().to_string(),
+        None => config
+            .get_string("user.name")
+            .map_err(|_| "user.name is not set (set author_name or git config)".to_string())?,
+    };
+    let email = match email {
+        Some(email) => email.trim().to_string(),
+        None => config
+            .get_string("user.email")
+            .map_err(|_| "user.email is not set (set author_email or git config)".to_string())?,
+    };
+    Signature::now(&name, &email).map_err(|error| error.message().to_string())
+}
+
+pub fn stash_index(repo: &mut Repository, stash: Oid) -> Option<usize> {
+    let mut position = None;
+    let _ = repo.stash_foreach(|index, _, oid| {
+        if *oid == stash {
+            position = Some(index);
+        }
+        position.is_none()
+    });
+    position
+}
+
+pub fn tag_exists(repo: &Repository, name: &str) -> bool {
+    repo.find_reference(&format!("refs/tags/{}", name)).is_ok()
+}
+
+pub fn is_valid_tag_name(name: &str) -> bool {
+    Reference::is_valid_name(&format!("refs/tags/{}", name))
+}
+
+pub fn tag(repo: &Repository, name: &str, force: bool) -> Result<(), String> {
+    let head = repo
+        .head()
+        .and_then(|head| head.peel(git2::ObjectType::Commit))
+        .map_err(|error| error.message().to_string())?;
+    repo.tag_lightweight(name, &head, force)
+        .map(|_| ())
+        .map_err(|error| error.message().to_string())
+}
+
+pub fn annotated_tag(repo: &Repository, name: &str, message: &str) -> Result<(), String> {
+    let head = repo
+        .head()
+        .and_then(|head| head.peel(git2::ObjectType::Commit))
+        .map_err(|error| error.message().to_string())?;
+    let tagger = signature(repo, None, None)?;
+    repo.tag(name, &head, &tagger, message, false)
+        .map(|_| ())
+        .map_err(|error| error.message().to_string())
+}
+
+pub fn remotes(repo: &Repository) -> Result<Vec<String>, String> {
+    let names = repo
+        .remotes()
+        .map_err(|error| error.message().to_string())?;
+    Ok(names.iter().flatten().map(str::to_string).collect())
+}
+
+pub fn remote_url(repo: &Repository, name: &str) -> Result<String, String> {
+    let remote = repo
+        .find_remote(name)
+        .map_err(|error| error.message().to_string())?;
+    remote
+        .url()
+        .map(str::to_string)
+        .ok_or_else(|| format!("remote '{}' has no URL", name))
+}
+
+pub fn set_remote(repo: &Repository, name: &str, url: &str) -> Result<(), String> {
+    let result = if repo.find_remote(name).is_ok() {
+        repo.remote_set_url(name, url)
+    } else {
+        repo.remote(name, url).map(|_| ())
+    };
+    result.map_err(|error| error.message().to_string())
+}
+
+// Errors keep the wording of the git CLI (`[rejected]`, `Authentication failed`)
+// so callers can classify them the same way.
+pub fn push(
+    repo: &Repository,
+    remote: &str,
+    refspecs: &[String],
+    token: Option<&str>,
+    passphrase: Option<&str>,
+    mut progress: impl FnMut(usize, usize, usize),
+) -> Result<(), String> {
+    let refspecs = refspecs
+        .iter()
+        .map(|refspec| full_refspec(repo, refspec))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut remote = repo
+        .find_remote(remote)
+        .map_err(|error| error.message().to_string())?;
+
+    let rejected = RefCell::new(Vec::new());
+    let mut attempts = CredentialAttempts::default();
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(|url, username, allowed| {
+        credentials(url, token, passphrase, username, allowed, &mut attempts)
+    });
+    callbacks.push_update_reference(|refname, status| {
+        if let Some(status) = status {
+            rejected
+                .borrow_mut()
+                .push(format!("[rejected] {} ({})", refname, status));
+        }
+        Ok(())
+    });
+    callbacks.push_transfer_progress(&mut progress);
+
+    let mut options = PushOptions::new();
+    options.remote_callbacks(callbacks);
+    let result = remote.push(&refspecs, Some(&mut options));
+    drop(options);
+    match result {
+        Err(error) if error.code() == ErrorCode::NotFastForward => {
+            Err(format!("[rejected] {} (non-fast-forward)", error.message()))
+        }
+        Err(error) if error.code() == ErrorCode::Auth => {
+            Err(format!("Authentication failed: {}", error.message()))
+        }
+        Err(error) => Err(error.message().to_string()),
+        Ok(()) if !rejected.borrow().is_empty() => Err(rejected.borrow().join("\n")),
+        Ok(()) => Ok(()),
+    }
+}
+
+pub const GIT_NOT_INSTALLED: &str = "git is not installed or not on PATH";
+
+pub fn git_spawn_error(error: std::io::Error) -> String {
+    if error.kind() == std::io::ErrorKind::NotFound {
+        GIT_NOT_INSTALLED.to_string()
+    } else {
+        error.to_string()
+    }
+}
+
+// Answers HTTPS credential prompts of the git CLI with the token in
+// `ERNEST_GIT_TOKEN`, so the token never appears in the command line.
+pub const GIT_TOKEN_CREDENTIAL_HELPER: &str =
+    "credential.helper=!f() { echo username=x-access-token; echo \"password=$ERNEST_GIT_TOKEN\"; }; f";
+
+// libgit2 gives up only when the callback errors, so cap the attempts.
+const MAX_CREDENTIAL_ATTEMPTS: usize = 4;
+const SSH_KEY_FILES: &[&str] = &["id_ed25519", "id_ecdsa", "id_rsa"];
+
+// libgit2 asks for a username before it asks for an SSH key, so each kind of
+// credential counts its own attempts.
+#[derive(Default)]
+struct CredentialAttempts {
+    plaintext: usize,
+    username: usize,
+    ssh_key: usize,
+}
+
+fn credentials(
+    url: &str,
+    token: Option<&str>,
+    passphrase: Option<&str>,
+    username: Option<&str>,
+    allowed: CredentialType,
+    attempts: &mut CredentialAttempts,
+) -> Result<Cred, git2::Error> {
+    let username = username.unwrap_or("git");
+    if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) {
+        attempts.plaintext += 1;
+        return match token {
+            Some(_) if attempts.plaintext > MAX_CREDENTIAL_ATTEMPTS => {
+                Err(git2::Error::from_str("Authentication failed"))
+            }
+            Some(token) => Cred::userpass_plaintext("x-access-token", token.trim()),
+            None => Err(git2::Error::from_str("could not read Username")),
+        };
+    }
+    if allowed.contains(CredentialType::USERNAME) {
+        attempts.username += 1;
+        if attempts.username > MAX_CREDENTIAL_ATTEMPTS {
+            return Err(git2::Error::from_str("Authentication failed"));
+        }
+        return Cred::username(username);
+    }
+    if allowed.contains(CredentialType::SSH_KEY) {
+        attempts.ssh_key += 1;
+        if attempts.ssh_key == 1 {
+            return Cred::ssh_key_from_agent(username);
+        }
+        return match ssh_key_files(url).get(attempts.ssh_key - 2) {
+            Some(key) => Cred::ssh_key(username, None, key, passphrase),
+            None => Err(git2::Error::from_str("Permission denied (publickey)")),
+        };
+    }
+    Cred::default()
+}
+
+fn ssh_key_files(url: &str) -> Vec<PathBuf> {
+    let home = PathBuf::from(std::env::var("HOME").unwrap_or_default());
+    let config = std::fs::read_to_string(home.join(".ssh/config")).unwrap_or_default();
+    let mut keys = match url_host(url) {
+        Some(host) => ssh_config_identities(&config, host, &home),
+        None => Vec::new(),
+    };
+    keys.extend(
+        SSH_KEY_FILES
+            .iter()
+            .map(|name| home.join(".ssh").join(name)),
+    );
+    let mut seen = Vec::new();
+    keys.retain(|key| {
+        let fresh = key.is_file() && !seen.contains(key);
+        seen.push(key.clone());
+        fresh
+    });
+    keys
+}
+
+fn url_host(url: &str) -> Option<&str> {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = rest.split('/').next()?;
+    let host = authority.rsplit('@').next()?.split(':').next()?;
+    (!host.is_empty()).then_some(host)
+}
+
+fn ssh_config_identities(config: &str, host: &str, home: &Path) -> Vec<PathBuf> {
+    let mut applies = true;
+    let mut identities = Vec::new();
+    for line in config.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = line
+            .split_once(|c: char| c.is_whitespace() || c == '=')
+            .unwrap_or((line, ""));
+        let value = value
+            .trim_start_matches(|c: char| c.is_whitespace() || c == '=')
+            .trim()
+            .trim_matches('"');
+        match key.to_ascii_lowercase().as_str() {
+            "host" => {
+                let patterns: Vec<&str> = value.split_whitespace().collect();
+                let negated = patterns.iter().any(|pattern| {
+                    pattern
+                        .strip_prefix('!')
+                        .is_some_and(|pattern| glob_matches(pattern, host))
+                });
+                applies = !negated
+                    && patterns
+                        .iter()
+                        .any(|pattern| !pattern.starts_with('!') && glob_matches(pattern, host));
+            }
+            "match" => applies = false,
+            "identityfile" if applies => {
+                let path = value
+                    .strip_prefix("~/")
+                    .or_else(|| value.strip_prefix("%d/"))
+                    .map_or_else(|| PathBuf::from(value), |rest| home.join(rest));
+                identities.push(path);
+            }
+            _ => {}
+        }
+    }
+    identities
+}
+
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    match pattern.chars().next() {
+        None => text.is_empty(),
+        Some('*') => {
+            let rest = &pattern[1..];
+            text.char_indices()
+                .map(|(index, _)| index)
+                .chain(std::iter::once(text.len()))
+                .any(|index| glob_matches(rest, &text[index..]))
+        }
+        Some(first) => {
+            let mut chars = text.chars();
+            match chars.next() {
+                Some(next) if first == '?' || first.eq_ignore_ascii_case(&next) => {
+                    glob_matches(&pattern[first.len_utf8()..], chars.as_str())
+                }
+                _ => false,
+            }
+        }
+    }
+}
+
+// libgit2 wants full ref names on both sides; expand `main`, `HEAD` and
+// `src:dst` shorthands the way the git CLI does.
+fn full_refspec(repo: &Repository, refspec: &str) -> Result<String, String> {
+    let (force, spec) = match refspec.strip_prefix('+') {
+        Some(spec) => ("+", spec),
+        None => ("", refspec),
+    };
+    let (source, destination) = match spec.split_once(':') {
+        Some((source, destination)) => (source, Some(destination)),
+        None => (spec, None),
+    };
+    let source = full_ref_name(repo, source)?;
+    let destination = match destination {
+        Some(destination) if destination.starts_with("refs/") => destination.to_string(),
+        Some(destination) => format!("refs/heads/{}", destination),
+        None => source.clone(),
+    };
+    Ok(format!("{}{}:{}", force, source, destination))
+}
+
+fn full_ref_name(repo: &Repository, name: &str) -> Result<String, String> {
+    if name.starts_with("refs/") {
+        return Ok(name.to_string());
+    }
+    if name == "HEAD" {
+        let head = repo.head().map_err(|error| error.message().to_string())?;
+        return head
+            .name()
+            .filter(|_| head.is_branch())
+            .map(str::to_string)
+            .ok_or_else(|| "HEAD is detached; set a branch or push refspec".to_string());
+    }
+    if repo.find_reference(&format!("refs/tags/{}", name)).is_ok() && !branch_exists(repo, name) {
+        return Ok(format!("refs/tags/{}", name));
+    }
+    Ok(format!("refs/heads/{}", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::process::Command;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn commit_with_paths_leaves_other_changes_staged() {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("ernest-test-git-partial-{}", suffix));
+        fs::create_dir_all(&dir).unwrap();
+        let repo = init(&dir).unwrap();
+        fs::write(dir.join("a.md"), "a").unwrap();
+        fs::write(dir.join("b.md"), "b").unwrap();
+        add(&repo, &["a.md".to_string(), "b.md".to_string()]).unwrap();
+
+        let paths = ["a.md".to_string()];
+        assert!(commit(
+            &repo,
+            "Add a",
+            &paths,
+            Some("Ernest"),
+            Some("e@example.com")
+        )
+        .unwrap());
+        assert!(!commit(
+            &repo,
+            "Again",
+            &paths,
+            Some("Ernest"),
+            Some("e@example.com")
+        )
+        .unwrap());
+
+        let entries = status(&repo).unwrap();
+        assert_eq!(
+            entries,
+            vec![StatusEntry {
+                code: "A ".to_string(),
+                path: "b.md".to_string(),
+            }]
+        );
+        assert_eq!(format_status(&entries), "A  b.md");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn reset_branch_hard_restores_an_earlier_commit() {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("ernest-test-git-rollback-{}", suffix));
+        fs::create_dir_all(&dir).unwrap();
+        let repo = init(&dir).unwrap();
+        reset_branch(&repo, "site").unwrap();
+        for content in ["first", "second"] {
+            fs::write(dir.join("index.html"), content).unwrap();
+            add_all(&repo).unwrap();
+            commit(&repo, content, &[], Some("Ernest"), Some("e@example.com")).unwrap();
+        }
+
+        let tip = branch_tip(&repo, "site").unwrap();
+        let previous = resolve_commit(&repo, &format!("{}~1", tip)).unwrap();
+        reset_branch_hard(&repo, "site", previous).unwrap();
+
+        assert_eq!(branch_tip(&repo, "site").unwrap(), previous);
+        assert_eq!(fs::read_to_string(dir.join("index.html")).unwrap(), "first");
+        assert!(status(&repo).unwrap().is_empty());
+        assert!(resolve_commit(&repo, &format!("{}~1", previous)).is_err());
+        assert!(branch_tip(&repo, "missing").is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn push_reports_a_diverged_branch_as_rejected() {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!("ernest-test-git-push-{}", suffix));
+        let remote = root.join("remote.git");
+        fs::create_dir_all(&remote).unwrap();
+        Repository::init_bare(&remote).unwrap();
+
+        let mut outputs = Vec::new();
+        for name in ["first", "second"] {
+            let dir = root.join(name);
+            fs::create_dir_all(&dir).unwrap();
+            let repo = init(&dir).unwrap();
+            fs::write(dir.join("index.html"), name).unwrap();
+            add_all(&repo).unwrap();
+            commit(&repo, name, &[], Some("Ernest"), Some("e@example.com")).unwrap();
+            set_remote(&repo, "origin", &remote.to_string_lossy()).unwrap();
+            let refspecs = ["HEAD:pages".to_string()];
+            outputs.push(push(&repo, "origin", &refspecs, None, None, |_, _, _| {}));
+        }
+
+        assert!(outputs[0].is_ok(), "{:?}", outputs[0]);
+        let error = outputs[1].clone().unwrap_err();
+        assert!(error.contains("[rejected]"), "{}", error);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn ssh_agent_is_tried_after_the_username_prompt() {
+        let mut attempts = CredentialAttempts::default();
+        let url = "ssh://git@git.example.com/site.git";
+        let username = credentials(
+            url,
+            None,
+            None,
+            None,
+            CredentialType::USERNAME,
+            &mut attempts,
+        )
+        .unwrap();
+        assert_eq!(username.credtype(), CredentialType::USERNAME.bits());
+
+        credentials(
+            url,
+            None,
+            None,
+            Some("git"),
+            CredentialType::SSH_KEY,
+            &mut attempts,
+        )
+        .ok();
+        assert_eq!(attempts.username, 1);
+        assert_eq!(attempts.ssh_key, 1);
+    }
+
+    #[test]
+    fn ssh_config_identities_follow_matching_hosts() {
+        let config = "\
+IdentityFile ~/.ssh/global
+Host *.example.com !private.example.com
+    IdentityFile ~/.ssh/example
+Host private.example.com
+  IdentityFile=\"/keys/private\"
+Match user deploy
+    IdentityFile ~/.ssh/never
+";
+        let home = Path::new("/home/ernest");
+        assert_eq!(
+            ssh_config_identities(config, "git.example.com", home),
+            vec![home.join(".ssh/global"), home.join(".ssh/example")]
+        );
+        assert_eq!(
+            ssh_config_identities(config, "private.example.com", home),
+            vec![home.join(".ssh/global"), PathBuf::from("/keys/private")]
+        );
+        assert_eq!(
+            url_host("ssh://git@host.example.com:2222/site.git"),
+            Some("host.example.com")
+        );
+        assert_eq!(
+            url_host("git@github.com:owner/site.git"),
+            Some("github.com")
+        );
+        assert!(glob_matches("git?ub.*", "github.com"));
+        assert!(!glob_matches("*.example.com", "example.com"));
+    }
+
+    #[test]
+    fn missing_git_binary_is_reported_plainly() {
+        let error = Command::new("ernest-missing-git-binary")
+            .output()
+            .unwrap_err();
+        assert_eq!(git_spawn_error(error), GIT_NOT_INSTALLED);
+    }
+}
@@ -0,0 +1,243 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::Local;
+
+use crate::export::{load_config, ExportErrorCode, ExportResponse, ExportTarget};
+
+const HISTORY_FILE: &str = ".export-history.jsonl";
+const DEFAULT_MAX_LOG_BYTES: u64 = 1024 * 1024;
+const DEFAULT_KEEP_GENERATIONS: usize = 3;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportHistoryEntry {
+    pub timestamp: String,
+    pub job_id: String,
+    pub file_path: String,
+    pub target: ExportTarget,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    pub ok: bool,
+    pub summary: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<ExportErrorCode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportHistoryRequest {
+    pub project_root: String,
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+#[tauri::command]
+pub fn get_export_history(
+    request: ExportHistoryRequest,
+) -> Result<Vec<ExportHistoryEntry>, String> {
+    let project_root = PathBuf::from(&request.project_root);
+    let mut entries = read_history(&project_root)?;
+    if let Some(label) = request
+        .label
+        .as_deref()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+    {
+        entries.retain(|entry| entry.label.as_deref() == Some(label));
+    }
+    entries.reverse();
+    if let Some(limit) = request.limit {
+        entries.truncate(limit);
+    }
+    Ok(entries)
+}
+
+#[tauri::command]
+pub fn clear_history(project_root: String) -> Result<(), String> {
+    let project_root = PathBuf::from(&project_root);
+    if !project_root.is_dir() {
+        return Err("Project root is missing".to_string());
+    }
+    let path = project_root.join(HISTORY_FILE);
+    for candidate in std::iter::once(path.clone()).chain((1..).map(|n| generation(&path, n))) {
+        if !candidate.exists() {
+            break;
+        }
+        fs::remove_file(&candidate).map_err(|error| error.to_string())?;
+    }
+    Ok(())
+}
+
+pub struct HistoryRecord<'a> {
+    pub job_id: &'a str,
+    pub file_path: &'a str,
+    pub target: &'a ExportTarget,
+    pub profile: Option<&'a str>,
+    pub label: Option<&'a str>,
+    pub response: &'a ExportResponse,
+    pub bytes: Option<u64>,
+    pub elapsed: Duration,
+}
+
+pub fn history_entry(record: &HistoryRecord) -> ExportHistoryEntry {
+    let HistoryRecord {
+        job_id,
+        file_path,
+        target,
+        profile,
+        label,
+        response,
+        bytes,
+        elapsed,
+    } = *record;
+    ExportHistoryEntry {
+        timestamp: Local::now().to_rfc3339(),
+        job_id: job_id.to_string(),
+        file_path: file_path.to_string(),
+        target: target.clone(),
+        profile: profile.map(|value| value.to_string()),
+        label: label.map(|value| value.to_string()),
+        ok: response.ok,
+        summary: response.summary.clone(),
+        error_code: response.error.as_ref().map(|error| error.code.clone()),
+        bytes,
+        duration_ms: Some(elapsed.as_millis() as u64),
+    }
+}
+
+const THROUGHPUT_SAMPLES: usize = 20;
+
+pub fn average_throughput(project_root: &Path, target: &ExportTarget) -> Option<(f64, usize)> {
+    let entries = read_history(project_root).ok()?;
+    let samples: Vec<(u64, u64)> = entries
+        .iter()
+        .rev()
+        .filter(|entry| entry.ok && &entry.target == target)
+        .filter_map(|entry| match (entry.bytes, entry.duration_ms) {
+            (Some(bytes), Some(duration_ms)) if bytes > 0 && duration_ms > 0 => {
+                Some((bytes, duration_ms))
+            }
+            _ => None,
+        })
+        .take(THROUGHPUT_SAMPLES)
+        .collect();
+    if samples.is_empty() {
+        return None;
+    }
+    let bytes: u64 = samples.iter().map(|(bytes, _)| bytes).sum();
+    let duration_ms: u64 = samples.iter().map(|(_, duration_ms)| duration_ms).sum();
+    Some((bytes as f64 / (duration_ms as f64 / 1000.0), samples.len()))
+}
+
+pub fn append_history(project_root: &Path, entry: &ExportHistoryEntry) -> Result<(), String> {
+    let line = serde_json::to_string(entry).map_err(|error| error.to_string())?;
+    let path = project_root.join(HISTORY_FILE);
+    let (max_bytes, keep) = retention_for(Some(project_root));
+    rotate_if_needed(&path, max_bytes, keep).map_err(|error| error.to_string())?;
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|error| error.to_string())?;
+    file.write_all(format!("{}\n", line).as_bytes())
+        .map_err(|error| error.to_string())
+}
+
+pub fn retention_for(project_root: Option<&Path>) -> (u64, usize) {
+    let retention = project_root
+        .and_then(|root| load_config(root).ok())
+        .and_then(|config| config.retention)
+        .unwrap_or_default();
+    (
+        retention.max_bytes.unwrap_or(DEFAULT_MAX_LOG_BYTES),
+        retention.keep.unwrap_or(DEFAULT_KEEP_GENERATIONS),
+    )
+}
+
+pub fn rotate_if_needed(path: &Path, max_bytes: u64, keep: usize) -> std::io::Result<()> {
+    let size = match fs::metadata(path) {
+        Ok(metadata) => metadata.len(),
+        Err(_) => return Ok(()),
+    };
+    if max_bytes == 0 || size < max_bytes {
+        return Ok(());
+    }
+    if keep == 0 {
+        return fs::remove_file(path);
+    }
+    let oldest = generation(path, keep);
+    if oldest.exists() {
+        fs::remove_file(&oldest)?;
+    }
+    for n in (1..keep).rev() {
+        let from = generation(path, n);
+        if from.exists() {
+            fs::rename(&from, generation(path, n + 1))?;
+        }
+    }
+    fs::rename(path, generation(path, 1))
+}
+
+fn generation(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+pub fn read_history(project_root: &Path) -> Result<Vec<ExportHistoryEntry>, String> {
+    let path = project_root.join(HISTORY_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(&path).map_err(|error| error.to_string())?;
+    Ok(content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn rotate_if_needed_keeps_limited_generations() {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("ernest-test-rotate-{}", suffix));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(".deploy.log");
+
+        for round in 0..4 {
+            fs::write(&path, format!("round {}", round)).unwrap();
+            rotate_if_needed(&path, 4, 2).unwrap();
+        }
+
+        assert!(!path.exists());
+        assert_eq!(fs::read_to_string(generation(&path, 1)).unwrap(), "round 3");
+        assert_eq!(fs::read_to_string(generation(&path, 2)).unwrap(), "round 2");
+        assert!(!generation(&path, 3).exists());
+
+        fs::write(&path, "ok").unwrap();
+        rotate_if_needed(&path, 1024, 2).unwrap();
+        assert!(path.exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,255 @@
+use serde::Deserialize;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const HOOK_POLL_INTERVAL: Duration = Duration::from_millis(50);
+const HOOK_OUTPUT_GRACE: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct HooksConfig {
+    #[serde(default)]
+    pub pre_export: Option<String>,
+
+    #[serde(default)]
+    pub post_export: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct HookOutput {
+    pub code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl HookOutput {
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+}
+
+pub fn run_hook(command: &str, cwd: &Path, cancel: &AtomicBool) -> Result<HookOutput, String> {
+    if cancel.load(Ordering::SeqCst) {
+        return Err("export_cancelled".to_string());
+    }
+
+    let mut child = shell_command(command)
+        .current_dir(cwd)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| format!("Unable to start hook: {}", error))?;
+
+    // Drain both pipes on their own threads so a chatty hook cannot block on a
+    // full pipe while we poll for exit.
+    let stdout = child.stdout.take().map(read_to_end);
+    let stderr = child.stderr.take().map(read_to_end);
+
+    let status = loop {
+        if cancel.load(Ordering::SeqCst) {
+            kill_tree(&mut child);
+            let _ = child.wait();
+            return Err("export_cancelled".to_string());
+        }
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) => thread::sleep(HOOK_POLL_INTERVAL),
+            Err(error) => return Err(error.to_string()),
+        }
+    };
+
+    // A background process started by the hook (`server &`) inherits the
+    // pipes and keeps them open, so only wait a moment for the readers.
+    let deadline = Instant::now() + HOOK_OUTPUT_GRACE;
+    while [&stdout, &stderr]
+        .into_iter()
+        .flatten()
+        .any(|reader| !reader.handle.is_finished())
+        && Instant::now() < deadline
+        && !cancel.load(Ordering::SeqCst)
+    {
+        thread::sleep(HOOK_POLL_INTERVAL);
+    }
+
+    Ok(HookOutput {
+        code: status.code(),
+        stdout: stdout.map(PipeReader::output).unwrap_or_default(),
+        stderr: stderr.map(PipeReader::output).unwrap_or_default(),
+    })
+}
+
+pub fn expand_hook_command(command: &str, files: &[&Path]) -> String {
+    if !command.contains("{file}") {
+        return command.to_string();
+    }
+    let quoted = files
+        .iter()
+        .map(|path| shell_quote(&path.to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join(" ");
+    command.replace("{file}", &quoted)
+}
+
+#[cfg(windows)]
+fn shell_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+#[cfg(not(windows))]
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+struct PipeReader {
+    buffer: Arc<Mutex<Vec<u8>>>,
+    handle: thread::JoinHandle<()>,
+}
+
+impl PipeReader {
+    // What was read so far; a reader still blocked on the pipe is left behind.
+    fn output(self) -> String {
+        let buffer = self.buffer.lock().expect("hook output lock poisoned");
+        String::from_utf8_lossy(&buffer).to_string()
+    }
+}
+
+fn read_to_end(mut pipe: impl Read + Send + 'static) -> PipeReader {
+    let buffer = Arc::new(Mutex::new(Vec::new()));
+    let shared = buffer.clone();
+    let handle = thread::spawn(move || {
+        let mut chunk = [0u8; 4096];
+        while let Ok(read) = pipe.read(&mut chunk) {
+            if read == 0 {
+                break;
+            }
+            shared
+                .lock()
+                .expect("hook output lock poisoned")
+                .extend_from_slice(&chunk[..read]);
+        }
+    });
+    PipeReader { buffer, handle }
+}
+
+// The shell is spawned as the leader of its own process group, so killing the
+// group also stops the commands it is running.
+#[cfg(not(windows))]
+fn kill_tree(child: &mut Child) {
+    let killed = Command::new("kill")
+        .args(["-KILL", "--", &format!("-{}", child.id())])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+    if !killed {
+        let _ = child.kill();
+    }
+}
+
+#[cfg(windows)]
+fn kill_tree(child: &mut Child) {
+    let killed = Command::new("taskkill")
+        .args(["/T", "/F", "/PID", &child.id().to_string()])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+    if !killed {
+        let _ = child.kill();
+    }
+}
+
+#[cfg(windows)]
+fn shell_command(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.args(["/C", command]);
+    shell
+}
+
+#[cfg(not(windows))]
+fn shell_command(command: &str) -> Command {
+    use std::os::unix::process::CommandExt;
+
+    let mut shell = Command::new("sh");
+    shell.args(["-c", command]).process_group(0);
+    shell
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_hook_captures_output_and_exit_code() {
+        let cwd = std::env::temp_dir();
+        let cancel = AtomicBool::new(false);
+
+        let output = run_hook("echo indexed", &cwd, &cancel).unwrap();
+        assert!(output.success());
+        assert_eq!(output.stdout.trim(), "indexed");
+
+        let output = run_hook("echo lint failed 1>&2 && exit 3", &cwd, &cancel).unwrap();
+        assert_eq!(output.code, Some(3));
+        assert_eq!(output.stderr.trim(), "lint failed");
+
+        cancel.store(true, Ordering::SeqCst);
+        assert_eq!(
+            run_hook("echo skipped", &cwd, &cancel).unwrap_err(),
+            "export_cancelled"
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn cancelling_a_hook_kills_the_commands_it_started() {
+        let cwd = std::env::temp_dir().join(format!("ernest-test-hook-{}", std::process::id()));
+        std::fs::create_dir_all(&cwd).unwrap();
+        let cancel = std::sync::Arc::new(AtomicBool::new(false));
+        let canceller = {
+            let cancel = cancel.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(300));
+                cancel.store(true, Ordering::SeqCst);
+            })
+        };
+
+        // The subshell sleeps as a child of `sh`; only a group kill stops it
+        // before it writes the marker.
+        let result = run_hook("(sleep 1; touch marker) & wait", &cwd, &cancel);
+        canceller.join().unwrap();
+        assert_eq!(result.unwrap_err(), "export_cancelled");
+        thread::sleep(Duration::from_millis(1200));
+        assert!(!cwd.join("marker").exists());
+
+        let _ = std::fs::remove_dir_all(&cwd);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn hook_returns_when_a_background_process_keeps_the_pipes_open() {
+        let cwd = std::env::temp_dir();
+        let cancel = AtomicBool::new(false);
+
+        let started = Instant::now();
+        let output = run_hook("echo ready; sleep 6 &", &cwd, &cancel).unwrap();
+        assert!(output.success());
+        assert_eq!(output.stdout.trim(), "ready");
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn expand_hook_command_quotes_file_paths() {
+        let files = [Path::new("/notes/it's done.md"), Path::new("/notes/b.md")];
+        assert_eq!(
+            expand_hook_command("purge {file}", &files),
+            "purge '/notes/it'\\''s done.md' '/notes/b.md'"
+        );
+        assert_eq!(expand_hook_command("make index", &files), "make index");
+    }
+}
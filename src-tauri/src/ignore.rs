@@ -0,0 +1,151 @@
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Default, Clone)]
+pub struct IgnoreRules {
+    patterns: Vec<IgnorePattern>,
+}
+
+#[derive(Debug, Clone)]
+struct IgnorePattern {
+    glob: String,
+    anchored: bool,
+    dir_only: bool,
+    negated: bool,
+}
+
+impl IgnoreRules {
+    pub fn load(project_root: &Path, file_name: &str) -> IgnoreRules {
+        match fs::read_to_string(project_root.join(file_name)) {
+            Ok(content) => IgnoreRules::parse(&content),
+            Err(_) => IgnoreRules::default(),
+        }
+    }
+
+    pub fn parse(content: &str) -> IgnoreRules {
+        let patterns = content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let (negated, line) = match line.strip_prefix('!') {
+                    Some(rest) => (true, rest),
+                    None => (false, line),
+                };
+                let dir_only = line.ends_with('/');
+                let line = line.trim_end_matches('/');
+                let anchored = line.contains('/');
+                IgnorePattern {
+                    glob: line.trim_start_matches('/').to_string(),
+                    anchored,
+                    dir_only,
+                    negated,
+                }
+            })
+            .filter(|pattern| !pattern.glob.is_empty())
+            .collect();
+        IgnoreRules { patterns }
+    }
+
+    pub fn with_patterns(mut self, patterns: &[String]) -> IgnoreRules {
+        let extra = IgnoreRules::parse(&patterns.join("\n"));
+        self.patterns.extend(extra.patterns);
+        self
+    }
+
+    // `relative` uses `/` separators and is relative to the project root.
+    // Later patterns win, so `!keep.png` can re-include a file.
+    pub fn is_ignored(&self, relative: &str, is_dir: bool) -> bool {
+        let mut ignored = false;
+        for pattern in &self.patterns {
+            if pattern.dir_only && !is_dir {
+                continue;
+            }
+            let matched = if pattern.anchored {
+                glob_match(&pattern.glob, relative)
+            } else {
+                let name = relative.rsplit('/').next().unwrap_or(relative);
+                glob_match(&pattern.glob, name)
+            };
+            if matched {
+                ignored = !pattern.negated;
+            }
+        }
+        ignored
+    }
+
+    pub fn is_ignored_with_parents(&self, relative: &str) -> bool {
+        let mut prefix = String::new();
+        let mut parts = relative.split('/').peekable();
+        while let Some(part) = parts.next() {
+            if !prefix.is_empty() {
+                prefix.push('/');
+            }
+            prefix.push_str(part);
+            let is_dir = parts.peek().is_some();
+            if self.is_ignored(&prefix, is_dir) {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    match_from(&pattern, &text)
+}
+
+fn match_from(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') if pattern.get(1) == Some(&'*') => {
+            let rest = pattern[2..].strip_prefix(&['/']).unwrap_or(&pattern[2..]);
+            (0..=text.len()).any(|index| match_from(rest, &text[index..]))
+        }
+        Some('*') => {
+            let mut index = 0;
+            loop {
+                if match_from(&pattern[1..], &text[index..]) {
+                    return true;
+                }
+                if index == text.len() || text[index] == '/' {
+                    return false;
+                }
+                index += 1;
+            }
+        }
+        Some('?') => !text.is_empty() && text[0] != '/' && match_from(&pattern[1..], &text[1..]),
+        Some(expected) => text.first() == Some(expected) && match_from(&pattern[1..], &text[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_ignored_matches_names_and_anchored_paths() {
+        let rules = IgnoreRules::parse("# drafts\n*.psd\ndrafts/\n/media/raw/**\n!keep.psd\n");
+        assert!(rules.is_ignored("art/cover.psd", false));
+        assert!(!rules.is_ignored("art/keep.psd", false));
+        assert!(rules.is_ignored("posts/drafts", true));
+        assert!(!rules.is_ignored("posts/drafts", false));
+        assert!(rules.is_ignored("media/raw/2024/shot.png", false));
+        assert!(!rules.is_ignored("media/shot.png", false));
+    }
+
+    #[test]
+    fn is_ignored_with_parents_covers_ignored_directories() {
+        let rules = IgnoreRules::parse(
+            "build/
+*.log
+",
+        );
+        assert!(rules.is_ignored_with_parents("build/img/a.png"));
+        assert!(rules.is_ignored_with_parents("posts/debug.log"));
+        assert!(!rules.is_ignored_with_parents("posts/build.png"));
+        assert!(!rules.is_ignored_with_parents("build"));
+    }
+}
@@ -1,13 +1,23 @@
 mod credentials;
 mod export;
+mod frontmatter;
+mod git;
+mod history;
+mod hooks;
+mod ignore;
+mod netlify;
+mod netrc;
+mod notify;
 mod project;
 mod publish;
+mod s3;
 
 fn main() {
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
         .manage(export::ExportJobs::default())
+        .manage(publish::PublishJobs::default())
         .setup(|app| {
             use tauri::menu::{Menu, MenuItem, Submenu};
 
@@ -281,13 +291,36 @@ Save: Writes the file to disk.";
         })
         .invoke_handler(tauri::generate_handler![
             export::export_file_async,
+            export::export_content_async,
+            export::export_all_async,
+            export::estimate_export,
             export::cancel_export,
             export::cleanup_export,
+            export::remote_diff,
+            frontmatter::apply_frontmatter_defaults,
+            history::get_export_history,
+            history::clear_history,
             credentials::get_credential,
+            credentials::credential_status,
             credentials::set_credential,
             credentials::delete_credential,
+            credentials::migrate_credentials,
+            credentials::list_credentials,
+            credentials::clear_project_credentials,
+            credentials::export_credentials,
+            credentials::import_credentials,
             publish::publish_project,
+            publish::publish_project_async,
+            publish::publish_and_export,
+            publish::cancel_publish,
+            publish::get_publish_defaults,
+            publish::restore_publish_backup,
+            publish::reveal_output,
             publish::deploy_project,
+            publish::deploy_project_async,
+            publish::cancel_deploy,
+            publish::find_orphan_assets,
+            publish::list_remote_branches,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,161 @@
+use sha1::{Digest, Sha1};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+const API_BASE: &str = "https://api.netlify.com/api/v1";
+
+pub struct DeployFile {
+    // Site path with a leading slash, as the deploy API expects.
+    pub path: String,
+    pub local_path: PathBuf,
+    pub sha1: String,
+    pub size: u64,
+}
+
+pub fn digest_files(dir: &Path) -> Result<Vec<DeployFile>, String> {
+    let mut files = Vec::new();
+    collect_files(dir, dir, &mut files)?;
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+// Symlinked folders are not followed, so a link loop cannot recurse forever;
+// symlinked files are uploaded with the content they point at.
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<DeployFile>) -> Result<(), String> {
+    for entry in fs::read_dir(dir).map_err(|error| error.to_string())? {
+        let entry = entry.map_err(|error| error.to_string())?;
+        let name = entry.file_name();
+        if name == ".git" || name == ".deploy.log" {
+            continue;
+        }
+        let path = entry.path();
+        let metadata = fs::symlink_metadata(&path).map_err(|error| error.to_string())?;
+        if metadata.is_dir() {
+            collect_files(root, &path, files)?;
+            continue;
+        }
+        if !metadata.is_file() && !path.is_file() {
+            continue;
+        }
+        let relative = match path.strip_prefix(root) {
+            Ok(relative) => relative.to_string_lossy().replace('\\', "/"),
+            Err(_) => continue,
+        };
+        let (sha1, size) = sha1_file(&path)?;
+        files.push(DeployFile {
+            path: format!("/{}", relative),
+            local_path: path,
+            sha1,
+            size,
+        });
+    }
+    Ok(())
+}
+
+fn sha1_file(path: &Path) -> Result<(String, u64), String> {
+    let mut file = fs::File::open(path).map_err(|error| error.to_string())?;
+    let mut hasher = Sha1::new();
+    let mut buffer = [0u8; 64 * 1024];
+    let mut size = 0u64;
+    loop {
+        let read = file.read(&mut buffer).map_err(|error| error.to_string())?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        size += read as u64;
+    }
+    Ok((hex::encode(hasher.finalize()), size))
+}
+
+pub fn deploys_url(site_id: &str) -> String {
+    format!("{}/sites/{}/deploys", API_BASE, site_id)
+}
+
+pub fn deploy_manifest(files: &[DeployFile]) -> String {
+    let manifest: serde_json::Map<String, serde_json::Value> = files
+        .iter()
+        .map(|file| {
+            (
+                file.path.clone(),
+                serde_json::Value::from(file.sha1.clone()),
+            )
+        })
+        .collect();
+    serde_json::json!({ "files": manifest }).to_string()
+}
+
+pub fn parse_deploy(body: &str) -> Option<(String, Vec<String>)> {
+    let value: serde_json::Value = serde_json::from_str(body).ok()?;
+    let id = value.get("id")?.as_str()?.to_string();
+    let required = value
+        .get("required")
+        .and_then(|required| required.as_array())
+        .map(|required| {
+            required
+                .iter()
+                .filter_map(|digest| digest.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default();
+    Some((id, required))
+}
+
+pub fn file_url(deploy_id: &str, path: &str) -> Result<reqwest::Url, String> {
+    let mut url = reqwest::Url::parse(&format!("{}/deploys/{}/files", API_BASE, deploy_id))
+        .map_err(|error| error.to_string())?;
+    if let Ok(mut segments) = url.path_segments_mut() {
+        segments.extend(path.split('/').filter(|segment| !segment.is_empty()));
+    }
+    Ok(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digest_files_hashes_nested_files_and_skips_git() {
+        let dir = std::env::temp_dir().join(format!("ernest-netlify-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(dir.join("posts")).unwrap();
+        fs::create_dir_all(dir.join(".git")).unwrap();
+        fs::create_dir_all(dir.join(".well-known")).unwrap();
+        fs::write(dir.join("posts/hello world.md"), "hello").unwrap();
+        fs::write(dir.join(".git/HEAD"), "ref").unwrap();
+        fs::write(dir.join(".well-known/security.txt"), "contact").unwrap();
+
+        let files = digest_files(&dir).unwrap();
+        let paths: Vec<&str> = files.iter().map(|file| file.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec!["/.well-known/security.txt", "/posts/hello world.md"]
+        );
+        fs::remove_dir_all(dir.join(".well-known")).unwrap();
+
+        let files = digest_files(&dir).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, "/posts/hello world.md");
+        assert_eq!(files[0].sha1, "aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d");
+        assert_eq!(
+            deploy_manifest(&files),
+            r#"{"files":{"/posts/hello world.md":"aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d"}}"#
+        );
+        assert_eq!(
+            file_url("d1", &files[0].path).unwrap().as_str(),
+            "https://api.netlify.com/api/v1/deploys/d1/files/posts/hello%20world.md"
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn parse_deploy_reads_required_digests() {
+        let body = r#"{"id":"d1","state":"uploading","required":["abc","def"]}"#;
+        assert_eq!(
+            parse_deploy(body),
+            Some(("d1".to_string(), vec!["abc".to_string(), "def".to_string()]))
+        );
+        assert_eq!(parse_deploy("not json"), None);
+    }
+}
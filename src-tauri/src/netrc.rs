@@ -0,0 +1,164 @@
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetrcEntry {
+    pub login: Option<String>,
+    pub password: Option<String>,
+}
+
+pub fn lookup_netrc(host: &str, login: Option<&str>) -> Option<NetrcEntry> {
+    let path = netrc_path()?;
+    if !has_private_permissions(&path) {
+        return None;
+    }
+    let content = fs::read_to_string(&path).ok()?;
+    find_entry(&content, host, login)
+}
+
+fn netrc_path() -> Option<PathBuf> {
+    if let Ok(value) = std::env::var("NETRC") {
+        if !value.trim().is_empty() {
+            return Some(PathBuf::from(value.trim()));
+        }
+    }
+    let home = std::env::var("HOME").ok()?;
+    let path = PathBuf::from(home).join(".netrc");
+    if path.exists() {
+        return Some(path);
+    }
+    None
+}
+
+#[cfg(unix)]
+fn has_private_permissions(path: &std::path::Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    match fs::metadata(path) {
+        Ok(metadata) => metadata.permissions().mode() & 0o077 == 0,
+        Err(_) => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn has_private_permissions(path: &std::path::Path) -> bool {
+    path.is_file()
+}
+
+fn find_entry(content: &str, host: &str, login: Option<&str>) -> Option<NetrcEntry> {
+    let login = login.map(str::trim).filter(|value| !value.is_empty());
+    let mut fallback = None;
+    for (machine, entry) in parse_entries(content) {
+        if let (Some(wanted), Some(found)) = (login, entry.login.as_deref()) {
+            if wanted != found {
+                continue;
+            }
+        }
+        match machine {
+            Some(name) if name.eq_ignore_ascii_case(host) => return Some(entry),
+            None if fallback.is_none() => fallback = Some(entry),
+            _ => {}
+        }
+    }
+    fallback
+}
+
+fn parse_entries(content: &str) -> Vec<(Option<String>, NetrcEntry)> {
+    let mut entries = Vec::new();
+    let mut current: Option<(Option<String>, NetrcEntry)> = None;
+    let mut lines = content.lines();
+
+    while let Some(line) = lines.next() {
+        let mut tokens = line.split_whitespace();
+        while let Some(token) = tokens.next() {
+            match token {
+                "machine" | "default" => {
+                    if let Some(entry) = current.take() {
+                        entries.push(entry);
+                    }
+                    let machine = if token == "machine" {
+                        tokens.next().map(|value| value.to_string())
+                    } else {
+                        None
+                    };
+                    current = Some((
+                        machine,
+                        NetrcEntry {
+                            login: None,
+                            password: None,
+                        },
+                    ));
+                }
+                "login" => {
+                    if let Some((_, entry)) = current.as_mut() {
+                        entry.login = tokens.next().map(|value| value.to_string());
+                    }
+                }
+                "password" => {
+                    if let Some((_, entry)) = current.as_mut() {
+                        entry.password = tokens.next().map(|value| value.to_string());
+                    }
+                }
+                "account" => {
+                    tokens.next();
+                }
+                "macdef" => {
+                    // Macro bodies run until the next blank line.
+                    for body in lines.by_ref() {
+                        if body.trim().is_empty() {
+                            break;
+                        }
+                    }
+                    break;
+                }
+                _ if token.starts_with('#') => break,
+                _ => {}
+            }
+        }
+    }
+
+    if let Some(entry) = current.take() {
+        entries.push(entry);
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\
+machine ftp.example.com login writer password secret
+machine other.example.com
+  login admin
+  password hunter2
+
+macdef init
+cd /pub
+
+default login anonymous password guest
+";
+
+    #[test]
+    fn find_entry_matches_machine() {
+        let entry = find_entry(SAMPLE, "other.example.com", None).unwrap();
+        assert_eq!(entry.login.as_deref(), Some("admin"));
+        assert_eq!(entry.password.as_deref(), Some("hunter2"));
+    }
+
+    #[test]
+    fn find_entry_respects_login_and_falls_back_to_default() {
+        let entry = find_entry(SAMPLE, "ftp.example.com", Some("writer")).unwrap();
+        assert_eq!(entry.password.as_deref(), Some("secret"));
+
+        assert!(find_entry(SAMPLE, "ftp.example.com", Some("someone")).is_none());
+
+        let entry = find_entry(SAMPLE, "unknown.example.com", None).unwrap();
+        assert_eq!(entry.login.as_deref(), Some("anonymous"));
+        assert_eq!(entry.password.as_deref(), Some("guest"));
+    }
+
+    #[test]
+    fn find_entry_skips_macro_bodies() {
+        assert!(find_entry("macdef x\nmachine fake password nope\n\n", "fake", None).is_none());
+    }
+}
@@ -1,16 +1,114 @@
 use std::path::{Path, PathBuf};
 
-pub fn find_project_root(file_path: &Path) -> Option<PathBuf> {
+pub const CONFIG_FILE_NAMES: &[&str] = &[".export.toml", "ernest.toml", ".ernest/export.toml"];
+
+pub const DEFAULT_MAX_DEPTH: usize = 40;
+
+const VCS_MARKERS: &[&str] = &[".git", ".hg", ".svn"];
+
+pub const PROJECT_CONFIG_MISSING: &str =
+    "No .export.toml, ernest.toml or .ernest/export.toml found in parent folders";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectLocation {
+    pub root: PathBuf,
+    pub config_path: PathBuf,
+}
+
+pub fn find_project(file_path: &Path) -> Option<ProjectLocation> {
+    find_project_within(file_path, DEFAULT_MAX_DEPTH)
+}
+
+// The walk stops after `max_depth` folders, at a repository root or at the
+// home directory, so a file on a large mounted share does not probe every
+// folder up to `/`.
+pub fn find_project_within(file_path: &Path, max_depth: usize) -> Option<ProjectLocation> {
     let start = if file_path.is_dir() {
         file_path
     } else {
         file_path.parent()?
     };
+    let home = std::env::var_os("HOME").map(PathBuf::from);
 
-    for ancestor in start.ancestors() {
-        if ancestor.join(".export.toml").exists() {
-            return Some(ancestor.to_path_buf());
+    for ancestor in start.ancestors().take(max_depth) {
+        if let Some(config_path) = find_config_file(ancestor) {
+            return Some(ProjectLocation {
+                root: ancestor.to_path_buf(),
+                config_path,
+            });
+        }
+        let at_boundary = home.as_deref() == Some(ancestor)
+            || VCS_MARKERS
+                .iter()
+                .any(|marker| ancestor.join(marker).exists());
+        if at_boundary {
+            break;
         }
     }
     None
 }
+
+pub fn find_project_root(file_path: &Path) -> Option<PathBuf> {
+    find_project(file_path).map(|location| location.root)
+}
+
+pub fn config_path(project_root: &Path) -> PathBuf {
+    find_config_file(project_root).unwrap_or_else(|| project_root.join(CONFIG_FILE_NAMES[0]))
+}
+
+fn find_config_file(dir: &Path) -> Option<PathBuf> {
+    CONFIG_FILE_NAMES
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn find_project_reports_the_matched_config_file() {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!("ernest-test-project-{}", suffix));
+        fs::create_dir_all(root.join(".ernest")).unwrap();
+        fs::create_dir_all(root.join("content/posts")).unwrap();
+        fs::write(root.join(".ernest/export.toml"), "version = 1\n").unwrap();
+        let file = root.join("content/posts/hello.md");
+        fs::write(&file, "Hello").unwrap();
+
+        let location = find_project(&file).unwrap();
+        assert_eq!(location.root, root);
+        assert_eq!(location.config_path, root.join(".ernest/export.toml"));
+
+        fs::write(root.join("ernest.toml"), "version = 1\n").unwrap();
+        assert_eq!(config_path(&root), root.join("ernest.toml"));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn find_project_stops_at_depth_and_repository_roots() {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let root = std::env::temp_dir().join(format!("ernest-test-project-bounds-{}", suffix));
+        let nested = root.join("repo/a/b");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(root.join(".export.toml"), "version = 1\n").unwrap();
+
+        assert_eq!(find_project_root(&nested), Some(root.clone()));
+        assert_eq!(find_project_within(&nested, 3), None);
+
+        fs::create_dir_all(root.join("repo/.git")).unwrap();
+        assert_eq!(find_project_root(&nested), None);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -1,41 +1,212 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
-use std::io::Write;
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, State};
 
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
 use chrono::Local;
+use git2::{Oid, Repository};
+use pulldown_cmark::html;
+use pulldown_cmark::{CowStr, Event, Options, Parser, Tag};
+use sha2::{Digest, Sha384};
 
-#[derive(Debug, Deserialize)]
+use crate::credentials::{
+    lookup_credential, CredentialKind, CredentialTarget, CREDENTIAL_STORE_LOCKED,
+};
+use crate::export::{
+    load_config, notify_success, run_export, sha256_hex, ExportErrorCode, ExportRequest,
+    ExportResponse, ExportTarget, PublishConfig,
+};
+use crate::frontmatter::{
+    drop_keys as drop_frontmatter_keys, scalar_value, split_frontmatter, string_values, strip_bom,
+};
+use crate::git::{self, StatusEntry};
+use crate::history::{retention_for, rotate_if_needed};
+use crate::ignore::IgnoreRules;
+
+#[derive(Debug, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct PublishRequest {
     pub project_root: String,
     pub files: Vec<String>,
     #[serde(default)]
     pub output_dir: Option<String>,
+    #[serde(default)]
+    pub base_path: Option<String>,
+    #[serde(default)]
+    pub keep_backup: bool,
+    #[serde(default)]
+    pub drop_keys: Vec<String>,
+    #[serde(default)]
+    pub integrity: bool,
+    #[serde(default)]
+    pub ignore_assets: Vec<String>,
+    #[serde(default)]
+    pub respect_gitignore: bool,
+    #[serde(default)]
+    pub render_html: bool,
+    #[serde(default)]
+    pub html_only: bool,
+    #[serde(default)]
+    pub flatten: bool,
+    #[serde(default)]
+    pub nojekyll: bool,
+    #[serde(default)]
+    pub cname: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublishBackupRequest {
+    pub project_root: String,
+    #[serde(default)]
+    pub output_dir: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RevealOutputRequest {
+    pub project_root: String,
+    #[serde(default)]
+    pub output_dir: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteBranchesRequest {
+    pub project_root: String,
+    #[serde(default)]
+    pub output_dir: Option<String>,
+    #[serde(default)]
+    pub remote: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum PublishErrorCode {
+    PublishCancelled,
+    ProjectRootMissing,
+    NoFilesSelected,
+    OutputDirInvalid,
+    OutputOutsideProject,
+    ConfigInvalid,
+    BackupFailed,
+    OutputNotWritable,
+    SourceUnreadable,
+    CopyFailed,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct PublishError {
+    pub code: PublishErrorCode,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct PublishResponse {
     pub ok: bool,
     pub summary: String,
     pub warnings: Vec<String>,
+    pub rewritten_urls: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub manifest_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<PublishError>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublishManifest {
+    pub generated_at: String,
+    pub file_count: usize,
+    pub asset_count: usize,
+    pub entries: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestEntry {
+    pub path: String,
+    pub kind: String,
+    pub size: u64,
+    pub sha256: String,
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
+pub struct PublishAndExportRequest {
+    pub publish_request: PublishRequest,
+    pub export_target: ExportTarget,
+    #[serde(default)]
+    pub profile: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PublishAndExportResponse {
+    pub ok: bool,
+    pub summary: String,
+    pub warnings: Vec<String>,
+    pub logs: Vec<String>,
+    pub publish: PublishResponse,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub export: Option<ExportResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deploy: Option<DeployResponse>,
+}
+
+const DEFAULT_PUSH_TIMEOUT_SECS: u64 = 120;
+const LS_REMOTE_TIMEOUT_SECS: u64 = 30;
+const GH_PAGES_BRANCH: &str = "gh-pages";
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
 pub struct DeployRequest {
     pub project_root: String,
     #[serde(default)]
     pub output_dir: Option<String>,
+    #[serde(default)]
     pub remote: String,
     #[serde(default)]
+    pub remotes: Vec<String>,
+    #[serde(default)]
     pub branch: Option<String>,
+    #[serde(default)]
+    pub push_timeout_secs: Option<u64>,
+    #[serde(default)]
+    pub message_template: Option<String>,
+    #[serde(default)]
+    pub message: Option<String>,
+    #[serde(default)]
+    pub gh_pages: bool,
+    #[serde(default)]
+    pub custom_domain: Option<String>,
+    #[serde(default)]
+    pub force: bool,
+    #[serde(default)]
+    pub tag: Option<String>,
+    #[serde(default)]
+    pub fail_on_existing_tag: bool,
+    #[serde(default)]
+    pub dry_run: bool,
+    #[serde(default)]
+    pub rollback_to: Option<String>,
+    #[serde(default)]
+    pub profile: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct DeployResponse {
     pub ok: bool,
@@ -43,393 +214,3615 @@ pub struct DeployResponse {
     pub logs: Vec<String>,
 }
 
-#[tauri::command]
-pub fn publish_project(request: PublishRequest) -> Result<PublishResponse, String> {
-    let project_root = PathBuf::from(&request.project_root);
-    if !project_root.exists() || !project_root.is_dir() {
-        return Err("Project root is missing".to_string());
-    }
-
-    if request.files.is_empty() {
-        return Err("No files selected for publish".to_string());
-    }
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PublishProgress {
+    pub job_id: String,
+    pub file_index: usize,
+    pub file_count: usize,
+    pub path: String,
+}
 
-    let output_dir = resolve_output_dir(&project_root, request.output_dir.as_deref())?;
-    fs::create_dir_all(&output_dir).map_err(|error| error.to_string())?;
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PublishFinished {
+    pub job_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<PublishResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
 
-    let mut warnings = Vec::new();
-    let mut copied_files = 0usize;
-    let mut copied_assets = 0usize;
-    let mut assets_seen: HashSet<PathBuf> = HashSet::new();
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DeployLog {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<String>,
+    pub line: String,
+}
 
-    let project_root_canon = project_root
-        .canonicalize()
-        .map_err(|error| error.to_string())?;
-    let output_dir_canon = output_dir
-        .canonicalize()
-        .map_err(|error| error.to_string())?;
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DeployFinished {
+    pub job_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<DeployResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
 
-    if !output_dir_canon.starts_with(&project_root_canon) {
-        return Err("Publish directory must stay inside the project root".to_string());
-    }
+// Publish and deploy jobs share one registry, so each job remembers its kind
+// and `cancel_publish` cannot stop a deploy (or the other way round).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JobKind {
+    Publish,
+    Deploy,
+}
 
-    for file in request.files {
-        let file_path = PathBuf::from(&file);
-        if !file_path.exists() {
-            warnings.push(format!("File not found: {}", file));
-            continue;
-        }
-        let file_canon = file_path
-            .canonicalize()
-            .map_err(|error| error.to_string())?;
-        if !file_canon.starts_with(&project_root_canon) {
-            warnings.push(format!("Skipped file outside project: {}", file));
-            continue;
-        }
+#[derive(Default)]
+pub struct PublishJobs {
+    jobs: Mutex<HashMap<String, (JobKind, Arc<AtomicBool>)>>,
+}
 
-        let relative = file_canon
-            .strip_prefix(&project_root_canon)
-            .map_err(|_| "Unable to resolve relative path".to_string())?;
-        let target = output_dir_canon.join(relative);
-        if let Some(parent) = target.parent() {
-            fs::create_dir_all(parent).map_err(|error| error.to_string())?;
-        }
-        fs::copy(&file_canon, &target).map_err(|error| error.to_string())?;
-        copied_files += 1;
+impl PublishJobs {
+    fn insert(&self, job_id: String, kind: JobKind, cancel: Arc<AtomicBool>) {
+        let mut jobs = self.jobs.lock().expect("publish jobs lock poisoned");
+        jobs.insert(job_id, (kind, cancel));
+    }
 
-        let content = fs::read_to_string(&file_canon).unwrap_or_default();
-        let assets = extract_local_assets(&content);
-        for asset in assets {
-            if let Some(asset_path) = resolve_asset_path(&project_root_canon, &file_canon, &asset) {
-                if !asset_path.exists() {
-                    warnings.push(format!("Missing asset: {}", asset));
-                    continue;
-                }
-                if !asset_path.is_file() {
-                    continue;
-                }
-                if !asset_path.starts_with(&project_root_canon) {
-                    warnings.push(format!("Skipped asset outside project: {}", asset));
-                    continue;
-                }
-                if assets_seen.insert(asset_path.clone()) {
-                    let rel_asset = asset_path
-                        .strip_prefix(&project_root_canon)
-                        .map_err(|_| "Unable to resolve asset path".to_string())?;
-                    let target_asset = output_dir_canon.join(rel_asset);
-                    if let Some(parent) = target_asset.parent() {
-                        fs::create_dir_all(parent).map_err(|error| error.to_string())?;
-                    }
-                    fs::copy(&asset_path, &target_asset).map_err(|error| error.to_string())?;
-                    copied_assets += 1;
-                }
+    fn cancel(&self, job_id: &str, kind: JobKind) -> Result<(), String> {
+        let jobs = self.jobs.lock().expect("publish jobs lock poisoned");
+        match jobs.get(job_id) {
+            Some((job_kind, cancel)) if *job_kind == kind => {
+                cancel.store(true, Ordering::SeqCst);
+                Ok(())
             }
+            _ if kind == JobKind::Deploy => Err("Unknown deploy job".to_string()),
+            _ => Err("Unknown publish job".to_string()),
         }
     }
 
-    let log_path = output_dir_canon.join(".deploy.log");
-    append_log(
-        &log_path,
-        "PUBLISH",
-        format!(
-            "Published {} file(s), {} asset(s)",
-            copied_files, copied_assets
-        )
-        .as_str(),
+    fn remove(&self, job_id: &str) {
+        let mut jobs = self.jobs.lock().expect("publish jobs lock poisoned");
+        jobs.remove(job_id);
+    }
+}
+
+#[tauri::command]
+pub fn publish_project(request: PublishRequest) -> Result<PublishResponse, String> {
+    run_publish(request, &AtomicBool::new(false), &|_, _, _| {})
+}
+
+#[tauri::command]
+pub fn publish_and_export(
+    app: AppHandle,
+    request: PublishAndExportRequest,
+) -> Result<PublishAndExportResponse, String> {
+    let project_root = request.publish_request.project_root.clone();
+    let output_dir = request.publish_request.output_dir.clone();
+
+    let publish = run_publish(
+        request.publish_request,
+        &AtomicBool::new(false),
+        &|_, _, _| {},
     )?;
+    let warnings = publish.warnings.clone();
+    if !publish.ok {
+        return Ok(PublishAndExportResponse {
+            ok: false,
+            summary: format!("Publish failed, export skipped: {}", publish.summary),
+            warnings,
+            logs: Vec::new(),
+            publish,
+            export: None,
+            deploy: None,
+        });
+    }
 
-    Ok(PublishResponse {
-        ok: true,
-        summary: format!(
-            "Published {} file(s) and {} asset(s)",
-            copied_files, copied_assets
-        ),
+    if matches!(request.export_target, ExportTarget::Git) {
+        let deploy = run_deploy(
+            DeployRequest {
+                project_root,
+                output_dir,
+                remote: String::new(),
+                remotes: Vec::new(),
+                branch: None,
+                push_timeout_secs: None,
+                message_template: None,
+                message: None,
+                gh_pages: false,
+                custom_domain: None,
+                force: false,
+                tag: None,
+                fail_on_existing_tag: false,
+                dry_run: false,
+                rollback_to: None,
+                profile: request.profile,
+            },
+            &AtomicBool::new(false),
+            &|line| emit_deploy_log(&app, None, line),
+        );
+        let deploy = match deploy {
+            Ok(deploy) => deploy,
+            Err(error) => DeployResponse {
+                ok: false,
+                summary: error.clone(),
+                logs: vec![error],
+            },
+        };
+        return Ok(PublishAndExportResponse {
+            ok: deploy.ok,
+            summary: format!("{}; {}", publish.summary, deploy.summary),
+            warnings,
+            logs: deploy.logs.clone(),
+            publish,
+            export: None,
+            deploy: Some(deploy),
+        });
+    }
+
+    let root = PathBuf::from(&project_root);
+    let output_dir = match output_dir {
+        Some(output_dir) => Some(output_dir),
+        None => load_publish_defaults(&root)?.output_dir,
+    };
+    let output_path = resolve_output_dir(&root, output_dir.as_deref())?;
+    let export_request = ExportRequest {
+        file_path: output_path.to_string_lossy().to_string(),
+        file_paths: Vec::new(),
+        target: request.export_target,
+        profile: request.profile,
+        label: None,
+        dry_run: false,
+        skip_pre_export: false,
+    };
+    let job_id = uuid::Uuid::new_v4().to_string();
+    let export = run_export(&app, &job_id, &export_request, &AtomicBool::new(false));
+    let logs = export
+        .logs
+        .iter()
+        .map(|log| match &log.detail {
+            Some(detail) => format!("{}: {}", log.message, detail),
+            None => log.message.clone(),
+        })
+        .collect();
+    Ok(PublishAndExportResponse {
+        ok: export.ok,
+        summary: format!("{}; {}", publish.summary, export.summary),
         warnings,
+        logs,
+        publish,
+        export: Some(export),
+        deploy: None,
+    })
+}
+
+#[tauri::command]
+pub fn publish_project_async(
+    app: AppHandle,
+    request: PublishRequest,
+    state: State<PublishJobs>,
+) -> Result<String, String> {
+    let job_id = uuid::Uuid::new_v4().to_string();
+    let cancel = Arc::new(AtomicBool::new(false));
+    state.insert(job_id.clone(), JobKind::Publish, cancel.clone());
+
+    let app_handle = app.clone();
+    let job_id_clone = job_id.clone();
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let result = run_publish(request, &cancel, &|file_index, file_count, path| {
+            let _ = app_handle.emit(
+                "publish:progress",
+                PublishProgress {
+                    job_id: job_id_clone.clone(),
+                    file_index,
+                    file_count,
+                    path: path.to_string_lossy().replace('\\', "/"),
+                },
+            );
+        });
+        app_handle.state::<PublishJobs>().remove(&job_id_clone);
+        let (response, error) = split_result(result);
+        let payload = PublishFinished {
+            job_id: job_id_clone,
+            response,
+            error,
+        };
+        let _ = app_handle.emit("publish:finished", payload);
+    });
+
+    Ok(job_id)
+}
+
+#[tauri::command]
+pub fn cancel_publish(job_id: String, state: State<PublishJobs>) -> Result<(), String> {
+    state.cancel(&job_id, JobKind::Publish)
+}
+
+#[tauri::command]
+pub fn deploy_project(app: AppHandle, request: DeployRequest) -> Result<DeployResponse, String> {
+    run_deploy(request, &AtomicBool::new(false), &|line| {
+        emit_deploy_log(&app, None, line)
     })
 }
 
 #[tauri::command]
-pub fn deploy_project(request: DeployRequest) -> Result<DeployResponse, String> {
+pub fn deploy_project_async(
+    app: AppHandle,
+    request: DeployRequest,
+    state: State<PublishJobs>,
+) -> Result<String, String> {
+    let job_id = uuid::Uuid::new_v4().to_string();
+    let cancel = Arc::new(AtomicBool::new(false));
+    state.insert(job_id.clone(), JobKind::Deploy, cancel.clone());
+
+    let app_handle = app.clone();
+    let job_id_clone = job_id.clone();
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let result = run_deploy(request, &cancel, &|line| {
+            emit_deploy_log(&app_handle, Some(&job_id_clone), line)
+        });
+        app_handle.state::<PublishJobs>().remove(&job_id_clone);
+        let (response, error) = split_result(result);
+        let payload = DeployFinished {
+            job_id: job_id_clone,
+            response,
+            error,
+        };
+        let _ = app_handle.emit("deploy:finished", payload);
+    });
+
+    Ok(job_id)
+}
+
+fn emit_deploy_log(app: &AppHandle, job_id: Option<&str>, line: &str) {
+    let _ = app.emit(
+        "deploy:log",
+        DeployLog {
+            job_id: job_id.map(str::to_string),
+            line: line.to_string(),
+        },
+    );
+}
+
+#[tauri::command]
+pub fn cancel_deploy(job_id: String, state: State<PublishJobs>) -> Result<(), String> {
+    state.cancel(&job_id, JobKind::Deploy)
+}
+
+#[tauri::command]
+pub fn get_publish_defaults(project_root: String) -> Result<PublishConfig, String> {
+    let project_root = PathBuf::from(&project_root);
+    if !project_root.is_dir() {
+        return Err("Project root is missing".to_string());
+    }
+    load_publish_defaults(&project_root)
+}
+
+#[tauri::command]
+pub fn restore_publish_backup(request: PublishBackupRequest) -> Result<String, String> {
     let project_root = PathBuf::from(&request.project_root);
     if !project_root.exists() || !project_root.is_dir() {
         return Err("Project root is missing".to_string());
     }
 
-    if request.remote.trim().is_empty() {
-        return Err("Deploy remote is missing".to_string());
+    let output_dir = resolve_output_dir(&project_root, request.output_dir.as_deref())?;
+    let backup_dir = backup_dir_for(&output_dir)?;
+    let retention = retention_for(Some(&project_root));
+    ensure_contained(&project_root, &output_dir)?;
+    ensure_contained(&project_root, &backup_dir)?;
+    if !backup_dir.is_dir() {
+        return Err("No publish backup found".to_string());
     }
 
-    let output_dir = resolve_output_dir(&project_root, request.output_dir.as_deref())?;
-    if !output_dir.exists() {
-        return Err("Publish directory does not exist. Run Publish first.".to_string());
+    // Swap the live output and the backup so a restore can itself be undone.
+    let swap_dir = suffixed_dir(&output_dir, ".swap")?;
+    if output_dir.exists() {
+        fs::rename(&output_dir, &swap_dir)
+            .map_err(|error| transfer_error(&output_dir, &swap_dir, error))?;
+    }
+    fs::rename(&backup_dir, &output_dir)
+        .map_err(|error| transfer_error(&backup_dir, &output_dir, error))?;
+    if swap_dir.exists() {
+        fs::rename(&swap_dir, &backup_dir)
+            .map_err(|error| transfer_error(&swap_dir, &backup_dir, error))?;
+        move_git_dir(&backup_dir, &output_dir)?;
     }
 
-    if std::env::var("SSH_AUTH_SOCK")
-        .unwrap_or_default()
-        .trim()
-        .is_empty()
+    append_log(
+        &output_dir.join(".deploy.log"),
+        retention,
+        "PUBLISH",
+        "Restored previous publish from backup",
+    )?;
+    Ok(format!(
+        "Restored publish backup into {}",
+        output_dir.display()
+    ))
+}
+
+#[tauri::command]
+pub fn reveal_output(request: RevealOutputRequest) -> Result<String, String> {
+    let project_root = PathBuf::from(&request.project_root);
+    if !project_root.exists() || !project_root.is_dir() {
+        return Err("Project root is missing".to_string());
+    }
+
+    let configured_output_dir = match request.output_dir {
+        Some(output_dir) => Some(output_dir),
+        None => load_publish_defaults(&project_root)?.output_dir,
+    };
+    let output_dir = resolve_output_dir(&project_root, configured_output_dir.as_deref())?;
+    if !output_dir.starts_with(&project_root)
+        || output_dir
+            .components()
+            .any(|component| matches!(component, std::path::Component::ParentDir))
     {
-        return Err("SSH agent not detected. Start ssh-agent first.".to_string());
+        return Err("Publish directory must stay inside the project root".to_string());
     }
+    fs::create_dir_all(&output_dir).map_err(|error| write_error(&output_dir, error))?;
+    ensure_contained(&project_root, &output_dir)?;
 
-    let mut logs = Vec::new();
-    let output_dir_canon = output_dir
+    let output_dir = output_dir
         .canonicalize()
         .map_err(|error| error.to_string())?;
+    open_in_file_manager(&output_dir)?;
+    Ok(output_dir.display().to_string())
+}
 
-    let git_dir = output_dir_canon.join(".git");
-    if !git_dir.exists() {
-        run_git_command(&output_dir_canon, &mut logs, &["init"])?;
+fn open_in_file_manager(path: &Path) -> Result<(), String> {
+    let program = if cfg!(target_os = "macos") {
+        "open"
+    } else if cfg!(target_os = "windows") {
+        "explorer"
+    } else {
+        "xdg-open"
+    };
+    Command::new(program)
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(|mut child| {
+            thread::spawn(move || {
+                let _ = child.wait();
+            });
+        })
+        .map_err(|error| format!("Unable to open file manager ({}): {}", program, error))
+}
+
+#[tauri::command]
+pub fn find_orphan_assets(project_root: String) -> Result<Vec<String>, String> {
+    let project_root = PathBuf::from(&project_root);
+    if !project_root.is_dir() {
+        return Err("Project root is missing".to_string());
+    }
+    let project_root = project_root
+        .canonicalize()
+        .map_err(|error| error.to_string())?;
+    let defaults = load_publish_defaults(&project_root)?;
+    let output_dir = resolve_output_dir(&project_root, defaults.output_dir.as_deref())?;
+    let mut skip_dirs = vec![output_dir.clone()];
+    if let Ok(backup_dir) = backup_dir_for(&output_dir) {
+        skip_dirs.push(backup_dir);
     }
+    let rules = IgnoreRules::load(&project_root, ".exportignore");
+    let asset_keys = frontmatter_asset_keys(&defaults);
 
-    let (remote_name, remote_url) = resolve_remote(&output_dir_canon, &request.remote, &mut logs)?;
+    let mut files = Vec::new();
+    collect_project_files(&project_root, &project_root, &skip_dirs, &rules, &mut files)?;
 
-    if !is_ssh_url(&remote_url) {
-        return Err("Deploy requires an SSH remote (git@ or ssh://)".to_string());
+    let mut referenced: HashSet<PathBuf> = HashSet::new();
+    for document in files
+        .iter()
+        .filter(|path| has_extension(path, DOCUMENT_EXTENSIONS))
+    {
+        let content = match fs::read_to_string(document) {
+            Ok(content) => content,
+            Err(_) => continue,
+        };
+        let content = strip_bom(&content);
+        let mut assets = extract_local_assets(content);
+        assets.extend(extract_frontmatter_assets(content, &asset_keys));
+        for asset in assets {
+            let target = asset.target.split(['?', '#']).next().unwrap_or_default();
+            if let Some(path) = resolve_asset_path(&project_root, document, target) {
+                if let Ok(path) = path.canonicalize() {
+                    referenced.insert(path);
+                }
+            }
+        }
     }
 
-    let branch = request
-        .branch
-        .clone()
-        .filter(|value| !value.trim().is_empty())
-        .unwrap_or_else(|| "main".to_string());
+    let mut orphans: Vec<String> = files
+        .iter()
+        .filter(|path| has_extension(path, ASSET_EXTENSIONS))
+        .filter(|path| !referenced.contains(*path))
+        .filter_map(|path| path.strip_prefix(&project_root).ok())
+        .map(|path| path.to_string_lossy().replace('\\', "/"))
+        .collect();
+    orphans.sort();
+    Ok(orphans)
+}
 
-    run_git_command(
-        &output_dir_canon,
-        &mut logs,
-        &["checkout", "-B", branch.as_str()],
-    )?;
+const DOCUMENT_EXTENSIONS: &[&str] = &["md", "markdown"];
+const ASSET_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "gif", "webp", "avif", "svg", "bmp", "ico", "pdf", "mp3", "mp4", "webm",
+    "ogg", "wav", "mov", "zip",
+];
 
-    run_git_command(&output_dir_canon, &mut logs, &["add", "-A"])?;
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| extensions.contains(&ext.to_ascii_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+fn collect_project_files(
+    project_root: &Path,
+    dir: &Path,
+    skip_dirs: &[PathBuf],
+    rules: &IgnoreRules,
+    files: &mut Vec<PathBuf>,
+) -> Result<(), String> {
+    for entry in fs::read_dir(dir).map_err(|error| error.to_string())? {
+        let path = entry.map_err(|error| error.to_string())?.path();
+        let is_dir = path.is_dir();
+        let hidden = path
+            .file_name()
+            .map(|name| name.to_string_lossy().starts_with('.'))
+            .unwrap_or(false);
+        if hidden || (is_dir && skip_dirs.contains(&path)) {
+            continue;
+        }
+        let relative = match path.strip_prefix(project_root) {
+            Ok(relative) => relative.to_string_lossy().replace('\\', "/"),
+            Err(_) => continue,
+        };
+        if rules.is_ignored(&relative, is_dir) {
+            continue;
+        }
+        if is_dir {
+            collect_project_files(project_root, &path, skip_dirs, rules, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn list_remote_branches(request: RemoteBranchesRequest) -> Result<Vec<String>, String> {
+    let project_root = PathBuf::from(&request.project_root);
+    if !project_root.exists() || !project_root.is_dir() {
+        return Err("Project root is missing".to_string());
+    }
+
+    let output_dir = resolve_output_dir(&project_root, request.output_dir.as_deref())?;
+    let remote = request
+        .remote
+        .as_deref()
+        .map(str::trim)
+        .filter(|remote| !remote.is_empty())
+        .unwrap_or("origin");
+    let looks_like_url = remote.contains("://") || remote.starts_with("git@");
+
+    let repo_path = if output_dir.join(".git").exists() {
+        output_dir
+    } else if looks_like_url {
+        project_root
+    } else {
+        return Ok(Vec::new());
+    };
+
+    let mut logs = DeployLogs::new(&|_| {});
+    if !looks_like_url
+        && git::open(&repo_path)
+            .and_then(|repo| git::remote_url(&repo, remote))
+            .is_err()
+    {
+        return Ok(Vec::new());
+    }
+
+    let output = run_git_command_watched(
+        &repo_path,
+        &mut logs,
+        &["ls-remote", "--heads", remote],
+        None,
+        Duration::from_secs(LS_REMOTE_TIMEOUT_SECS),
+        &AtomicBool::new(false),
+    )?;
+
+    Ok(output
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .filter_map(|reference| reference.strip_prefix("refs/heads/"))
+        .map(|branch| branch.to_string())
+        .collect())
+}
+
+fn publish_failure(
+    code: PublishErrorCode,
+    message: &str,
+    detail: Option<String>,
+) -> PublishResponse {
+    PublishResponse {
+        ok: false,
+        summary: message.to_string(),
+        warnings: Vec::new(),
+        rewritten_urls: 0,
+        manifest_path: None,
+        error: Some(PublishError {
+            code,
+            message: message.to_string(),
+            detail,
+        }),
+    }
+}
+
+enum PublishFailure {
+    Failed(PublishError),
+    Unexpected(String),
+}
+
+impl From<String> for PublishFailure {
+    fn from(error: String) -> Self {
+        PublishFailure::Unexpected(error)
+    }
+}
+
+fn failed(code: PublishErrorCode, message: &'static str) -> impl Fn(String) -> PublishFailure {
+    move |detail| {
+        PublishFailure::Failed(PublishError {
+            code: code.clone(),
+            message: message.to_string(),
+            detail: Some(detail),
+        })
+    }
+}
+
+fn split_result<T>(result: Result<T, String>) -> (Option<T>, Option<String>) {
+    match result {
+        Ok(response) => (Some(response), None),
+        Err(error) => (None, Some(error)),
+    }
+}
+
+// A cancelled publish keeps every file copied before the flag was seen; the
+// output directory is left partially updated and the log records the stop.
+fn run_publish(
+    request: PublishRequest,
+    cancel: &AtomicBool,
+    progress: &(dyn Fn(usize, usize, &Path) + Sync),
+) -> Result<PublishResponse, String> {
+    match publish_output(request, cancel, progress) {
+        Ok(response) => Ok(response),
+        Err(PublishFailure::Failed(error)) => {
+            Ok(publish_failure(error.code, &error.message, error.detail))
+        }
+        Err(PublishFailure::Unexpected(error)) => Err(error),
+    }
+}
+
+fn publish_output(
+    request: PublishRequest,
+    cancel: &AtomicBool,
+    progress: &(dyn Fn(usize, usize, &Path) + Sync),
+) -> Result<PublishResponse, PublishFailure> {
+    let project_root = PathBuf::from(&request.project_root);
+    if !project_root.exists() || !project_root.is_dir() {
+        return Ok(publish_failure(
+            PublishErrorCode::ProjectRootMissing,
+            "Project root is missing",
+            Some(request.project_root.clone()),
+        ));
+    }
+
+    if request.files.is_empty() {
+        return Ok(publish_failure(
+            PublishErrorCode::NoFilesSelected,
+            "No files selected for publish",
+            None,
+        ));
+    }
+
+    let defaults = load_publish_defaults(&project_root).map_err(failed(
+        PublishErrorCode::ConfigInvalid,
+        "Invalid publish configuration",
+    ))?;
+    let retention = retention_for(Some(&project_root));
+    let asset_keys = frontmatter_asset_keys(&defaults);
+    let configured_output_dir = request.output_dir.clone().or(defaults.output_dir);
+    let integrity = request.integrity || defaults.integrity.unwrap_or(false);
+    let render_html = request.render_html || defaults.render.unwrap_or(false);
+    let flatten = request.flatten || defaults.flatten.unwrap_or(false);
+    let manifest_name = defaults
+        .manifest_name
+        .clone()
+        .unwrap_or_else(|| DEFAULT_MANIFEST_FILE_NAME.to_string());
+    let drop_keys = if request.drop_keys.is_empty() {
+        defaults.drop_keys
+    } else {
+        request.drop_keys.clone()
+    };
+    let output_dir = match resolve_output_dir(&project_root, configured_output_dir.as_deref()) {
+        Ok(output_dir) => output_dir,
+        Err(error) => {
+            return Ok(publish_failure(
+                PublishErrorCode::OutputDirInvalid,
+                &error,
+                None,
+            ))
+        }
+    };
+    let backup_dir = if request.keep_backup && output_dir.exists() {
+        if let Err(error) = ensure_contained(&project_root, &output_dir) {
+            return Ok(publish_failure(
+                PublishErrorCode::OutputOutsideProject,
+                &error,
+                Some(output_dir.display().to_string()),
+            ));
+        }
+        Some(rotate_backup(&project_root, &output_dir).map_err(failed(
+            PublishErrorCode::BackupFailed,
+            "Unable to back up the previous output",
+        ))?)
+    } else {
+        None
+    };
+    fs::create_dir_all(&output_dir)
+        .map_err(|error| write_error(&output_dir, error))
+        .map_err(failed(
+            PublishErrorCode::OutputNotWritable,
+            "Unable to write the publish output",
+        ))?;
+
+    let mut warnings = Vec::new();
+    let mut copied_files = 0usize;
+    let mut published_files = Vec::new();
+    let mut rewritten_urls = 0usize;
+    let base_path = normalize_base_path(request.base_path.as_deref());
+    let mut assets_seen: HashSet<PathBuf> = HashSet::new();
+    // `.publishignore` is read first and the request's `ignore_assets` after
+    // it, so a request pattern (including `!pattern`) wins over the file.
+    let asset_rules =
+        IgnoreRules::load(&project_root, ".publishignore").with_patterns(&request.ignore_assets);
+    let gitignore = request
+        .respect_gitignore
+        .then(|| Gitignore::load(&project_root));
+    let mut gitignored = Vec::new();
+
+    let project_root_canon = project_root
+        .canonicalize()
+        .map_err(|error| read_error(&project_root, error))
+        .map_err(failed(
+            PublishErrorCode::ProjectRootMissing,
+            "Project root is missing",
+        ))?;
+    let output_dir_canon = output_dir
+        .canonicalize()
+        .map_err(|error| read_error(&output_dir, error))
+        .map_err(failed(
+            PublishErrorCode::OutputDirInvalid,
+            "Unable to resolve the publish directory",
+        ))?;
+
+    if !output_dir_canon.starts_with(&project_root_canon) {
+        return Ok(publish_failure(
+            PublishErrorCode::OutputOutsideProject,
+            "Publish directory must stay inside the project root",
+            Some(output_dir_canon.display().to_string()),
+        ));
+    }
+
+    if let Some(backup_dir) = &backup_dir {
+        append_log(
+            &output_dir_canon.join(".deploy.log"),
+            retention,
+            "PUBLISH",
+            format!("Backed up previous output to {}", backup_dir.display()).as_str(),
+        )
+        .map_err(failed(
+            PublishErrorCode::OutputNotWritable,
+            "Unable to write the publish output",
+        ))?;
+    }
+
+    let file_count = request.files.len();
+    let mut copied_assets = 0usize;
+    let mut published_assets = Vec::new();
+    let mut flattened: HashMap<PathBuf, String> = HashMap::new();
+    for (file_index, file) in request.files.into_iter().enumerate() {
+        if cancel.load(Ordering::SeqCst) {
+            return publish_cancelled(
+                &output_dir_canon,
+                retention,
+                copied_files,
+                copied_assets,
+                warnings,
+                rewritten_urls,
+            )
+            .map_err(failed(
+                PublishErrorCode::OutputNotWritable,
+                "Unable to write the publish output",
+            ));
+        }
+
+        let file_path = PathBuf::from(&file);
+        if !file_path.exists() {
+            warnings.push(format!("File not found: {}", file));
+            continue;
+        }
+        let file_canon = file_path
+            .canonicalize()
+            .map_err(|error| read_error(&file_path, error))
+            .map_err(failed(
+                PublishErrorCode::SourceUnreadable,
+                "Unable to read a selected file",
+            ))?;
+        if !file_canon.starts_with(&project_root_canon) {
+            warnings.push(format!("Skipped file outside project: {}", file));
+            continue;
+        }
+
+        let relative = file_canon
+            .strip_prefix(&project_root_canon)
+            .map_err(|_| "Unable to resolve relative path".to_string())?;
+        if is_gitignored(gitignore.as_ref(), relative) {
+            gitignored.push(relative.to_string_lossy().replace('\\', "/"));
+            continue;
+        }
+        // Flattened documents keep only their file name; their assets are
+        // placed relative to the document's folder so links still resolve.
+        let document_dir = file_canon.parent().unwrap_or(&project_root_canon);
+        let target = match (flatten, file_canon.file_name()) {
+            (true, Some(name)) => {
+                let target = output_dir_canon.join(name);
+                if let Some(first) = flattened.get(&target) {
+                    warnings.push(format!(
+                        "Skipped {}: flattened name collides with {}",
+                        file, first
+                    ));
+                    continue;
+                }
+                flattened.insert(target.clone(), file.clone());
+                target
+            }
+            _ => output_dir_canon.join(relative),
+        };
+        progress(file_index, file_count, relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|error| write_error(parent, error))
+                .map_err(failed(
+                    PublishErrorCode::OutputNotWritable,
+                    "Unable to write the publish output",
+                ))?;
+        }
+        let raw_content = match fs::read_to_string(&file_canon) {
+            Ok(content) => content,
+            Err(error) if error.kind() == std::io::ErrorKind::InvalidData => {
+                warnings.push(format!("Copied {} unchanged: not valid UTF-8", file));
+                fs::copy(&file_canon, &target)
+                    .map_err(|error| transfer_error(&file_canon, &target, error))
+                    .map_err(failed(
+                        PublishErrorCode::CopyFailed,
+                        "Unable to copy a file",
+                    ))?;
+                published_files.push(target);
+                copied_files += 1;
+                continue;
+            }
+            Err(error) => {
+                return Err(failed(
+                    PublishErrorCode::SourceUnreadable,
+                    "Unable to read a selected file",
+                )(read_error(&file_canon, error)))
+            }
+        };
+        let content = strip_bom(&raw_content);
+        let mut output = (content.len() != raw_content.len()).then(|| content.to_string());
+        if !drop_keys.is_empty() {
+            let (stripped, removed) = drop_frontmatter_keys(content, &drop_keys);
+            if !removed.is_empty() {
+                warnings.push(format!(
+                    "Dropped frontmatter keys from {}: {}",
+                    file,
+                    removed.join(", ")
+                ));
+                output = Some(stripped);
+            }
+        }
+        if let Some(base) = base_path.as_deref() {
+            let (rewritten, count) = rewrite_base_path(output.as_deref().unwrap_or(content), base);
+            output = Some(rewritten);
+            rewritten_urls += count;
+        }
+        let render = render_html && has_extension(&file_canon, DOCUMENT_EXTENSIONS);
+        if render {
+            let html_target = target.with_extension("html");
+            let title = file_canon
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let html = render_markdown_html(output.as_deref().unwrap_or(content), &title);
+            fs::write(&html_target, html)
+                .map_err(|error| write_error(&html_target, error))
+                .map_err(failed(
+                    PublishErrorCode::OutputNotWritable,
+                    "Unable to write the publish output",
+                ))?;
+            published_files.push(html_target);
+        }
+        if !(render && request.html_only) {
+            match output {
+                Some(output) => {
+                    fs::write(&target, output)
+                        .map_err(|error| write_error(&target, error))
+                        .map_err(failed(
+                            PublishErrorCode::OutputNotWritable,
+                            "Unable to write the publish output",
+                        ))?;
+                }
+                None => {
+                    fs::copy(&file_canon, &target)
+                        .map_err(|error| transfer_error(&file_canon, &target, error))
+                        .map_err(failed(
+                            PublishErrorCode::CopyFailed,
+                            "Unable to copy a file",
+                        ))?;
+                }
+            }
+            published_files.push(target.clone());
+        }
+        copied_files += 1;
+
+        let mut assets = extract_local_assets(content);
+        assets.extend(extract_frontmatter_assets(content, &asset_keys));
+        let mut asset_copies = Vec::new();
+        for asset in assets {
+            if let Some(asset_path) =
+                resolve_asset_path(&project_root_canon, &file_canon, &asset.target)
+            {
+                let ignored = asset_path
+                    .strip_prefix(&project_root_canon)
+                    .map(|relative| {
+                        asset_rules
+                            .is_ignored(&relative.to_string_lossy().replace('\\', "/"), false)
+                    })
+                    .unwrap_or(false);
+                if ignored {
+                    continue;
+                }
+                if let Ok(relative) = asset_path.strip_prefix(&project_root_canon) {
+                    if is_gitignored(gitignore.as_ref(), relative) {
+                        let relative = relative.to_string_lossy().replace('\\', "/");
+                        if !gitignored.contains(&relative) {
+                            gitignored.push(relative);
+                        }
+                        continue;
+                    }
+                }
+                if !asset_path.exists() {
+                    warnings.push(format!(
+                        "Missing asset: {} ({}:{})",
+                        asset.target, file, asset.line
+                    ));
+                    continue;
+                }
+                if !asset_path.is_file() {
+                    continue;
+                }
+                if !asset_path.starts_with(&project_root_canon) {
+                    warnings.push(format!(
+                        "Skipped asset outside project: {} ({}:{})",
+                        asset.target, file, asset.line
+                    ));
+                    continue;
+                }
+                // The check above is lexical; a symlink (or a symlinked
+                // directory) inside the project may still point outside it.
+                if resolves_outside(&asset_path, &project_root_canon) {
+                    warnings.push(format!(
+                        "Skipped symlinked asset outside project: {} ({}:{})",
+                        asset.target, file, asset.line
+                    ));
+                    continue;
+                }
+                if assets_seen.insert(asset_path.clone()) {
+                    let rel_asset = asset_path
+                        .strip_prefix(&project_root_canon)
+                        .map_err(|_| "Unable to resolve asset path".to_string())?;
+                    let output_asset = match asset_path.strip_prefix(document_dir) {
+                        Ok(beside_document) if flatten => beside_document,
+                        _ => rel_asset,
+                    };
+                    asset_copies.push(AssetCopy {
+                        file_index,
+                        target: output_dir_canon.join(output_asset),
+                        relative: rel_asset.to_path_buf(),
+                        source: asset_path,
+                    });
+                }
+            }
+        }
+
+        // Each document's assets are copied before the next document, so
+        // progress only moves forward and a cancel leaves whole pages behind.
+        let copied = copy_assets(&asset_copies, file_count, cancel, progress).map_err(failed(
+            PublishErrorCode::CopyFailed,
+            "Unable to copy an asset",
+        ))?;
+        copied_assets += copied;
+        if copied < asset_copies.len() {
+            return publish_cancelled(
+                &output_dir_canon,
+                retention,
+                copied_files,
+                copied_assets,
+                warnings,
+                rewritten_urls,
+            )
+            .map_err(failed(
+                PublishErrorCode::OutputNotWritable,
+                "Unable to write the publish output",
+            ));
+        }
+        published_assets.extend(asset_copies.into_iter().map(|copy| copy.target));
+    }
+
+    if !gitignored.is_empty() {
+        warnings.push(format!(
+            "Skipped gitignored paths: {}",
+            gitignored.join(", ")
+        ));
+    }
+
+    if integrity {
+        let html_files: Vec<PathBuf> = published_files
+            .iter()
+            .filter(|path| has_extension(path, &["html", "htm"]))
+            .cloned()
+            .collect();
+        write_integrity(&output_dir_canon, &html_files, &mut warnings).map_err(failed(
+            PublishErrorCode::OutputNotWritable,
+            "Unable to write the publish output",
+        ))?;
+    }
+
+    let control_files = write_pages_files(
+        &output_dir_canon,
+        request.nojekyll,
+        request.cname.as_deref(),
+    )
+    .map_err(failed(
+        PublishErrorCode::OutputNotWritable,
+        "Unable to write the publish output",
+    ))?
+    .len();
+
+    // Written last so the hashes cover any integrity attributes added above.
+    let manifest_path = output_dir_canon.join(&manifest_name);
+    let manifest_path = if published_files.contains(&manifest_path) {
+        warnings.push(format!(
+            "Skipped the publish manifest: {} is a published file; set [publish] manifest_name",
+            manifest_name
+        ));
+        None
+    } else {
+        write_manifest(
+            &manifest_path,
+            &output_dir_canon,
+            &published_files,
+            &published_assets,
+        )
+        .map_err(failed(
+            PublishErrorCode::OutputNotWritable,
+            "Unable to write the publish output",
+        ))?;
+        Some(manifest_path)
+    };
+
+    let log_path = output_dir_canon.join(".deploy.log");
+    append_log(
+        &log_path,
+        retention,
+        "PUBLISH",
+        format!(
+            "Published {} file(s), {} asset(s)",
+            copied_files, copied_assets
+        )
+        .as_str(),
+    )
+    .map_err(failed(
+        PublishErrorCode::OutputNotWritable,
+        "Unable to write the publish output",
+    ))?;
+
+    let summary = if control_files > 0 {
+        format!(
+            "Published {} file(s), {} asset(s) and {} control file(s)",
+            copied_files, copied_assets, control_files
+        )
+    } else {
+        format!(
+            "Published {} file(s) and {} asset(s)",
+            copied_files, copied_assets
+        )
+    };
+    Ok(PublishResponse {
+        ok: true,
+        summary,
+        warnings,
+        rewritten_urls,
+        manifest_path: manifest_path.map(|path| path.display().to_string()),
+        error: None,
+    })
+}
+
+const DEFAULT_MANIFEST_FILE_NAME: &str = "manifest.json";
+
+fn write_manifest(
+    manifest_path: &Path,
+    output_dir: &Path,
+    files: &[PathBuf],
+    assets: &[PathBuf],
+) -> Result<(), String> {
+    let mut entries = Vec::new();
+    for (kind, paths) in [("file", files), ("asset", assets)] {
+        for path in paths {
+            let file = fs::File::open(path).map_err(|error| read_error(path, error))?;
+            let size = file.metadata().map_err(|error| error.to_string())?.len();
+            entries.push(ManifestEntry {
+                path: path
+                    .strip_prefix(output_dir)
+                    .unwrap_or(path)
+                    .to_string_lossy()
+                    .replace('\\', "/"),
+                kind: kind.to_string(),
+                size,
+                sha256: sha256_hex(file)?,
+            });
+        }
+    }
+    let manifest = PublishManifest {
+        generated_at: Local::now().to_rfc3339(),
+        file_count: files.len(),
+        asset_count: assets.len(),
+        entries,
+    };
+    let json = serde_json::to_string_pretty(&manifest).map_err(|error| error.to_string())?;
+    fs::write(manifest_path, format!("{}\n", json))
+        .map_err(|error| write_error(manifest_path, error))
+}
+
+fn publish_cancelled(
+    output_dir: &Path,
+    retention: (u64, usize),
+    copied_files: usize,
+    copied_assets: usize,
+    warnings: Vec<String>,
+    rewritten_urls: usize,
+) -> Result<PublishResponse, String> {
+    let message = format!(
+        "Publish cancelled after {} file(s), {} asset(s)",
+        copied_files, copied_assets
+    );
+    append_log(
+        &output_dir.join(".deploy.log"),
+        retention,
+        "PUBLISH",
+        &message,
+    )?;
+    Ok(PublishResponse {
+        ok: false,
+        summary: message.clone(),
+        warnings,
+        rewritten_urls,
+        manifest_path: None,
+        error: Some(PublishError {
+            code: PublishErrorCode::PublishCancelled,
+            message,
+            detail: None,
+        }),
+    })
+}
+
+struct AssetCopy {
+    file_index: usize,
+    source: PathBuf,
+    target: PathBuf,
+    relative: PathBuf,
+}
+
+const MAX_COPY_THREADS: usize = 8;
+
+fn copy_assets(
+    copies: &[AssetCopy],
+    file_count: usize,
+    cancel: &AtomicBool,
+    progress: &(dyn Fn(usize, usize, &Path) + Sync),
+) -> Result<usize, String> {
+    let threads = thread::available_parallelism()
+        .map(|count| count.get())
+        .unwrap_or(1)
+        .clamp(1, MAX_COPY_THREADS)
+        .min(copies.len());
+    let next = AtomicUsize::new(0);
+    let copied = AtomicUsize::new(0);
+    let failure: Mutex<Option<String>> = Mutex::new(None);
+    let copy_one = |copy: &AssetCopy| -> Result<(), String> {
+        if let Some(parent) = copy.target.parent() {
+            fs::create_dir_all(parent).map_err(|error| write_error(parent, error))?;
+        }
+        progress(copy.file_index, file_count, &copy.relative);
+        fs::copy(&copy.source, &copy.target)
+            .map_err(|error| transfer_error(&copy.source, &copy.target, error))?;
+        Ok(())
+    };
+
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| loop {
+                if cancel.load(Ordering::SeqCst) || failure.lock().map_or(true, |f| f.is_some()) {
+                    break;
+                }
+                let copy = match copies.get(next.fetch_add(1, Ordering::SeqCst)) {
+                    Some(copy) => copy,
+                    None => break,
+                };
+                match copy_one(copy) {
+                    Ok(()) => {
+                        copied.fetch_add(1, Ordering::SeqCst);
+                    }
+                    Err(error) => {
+                        if let Ok(mut failure) = failure.lock() {
+                            failure.get_or_insert(error);
+                        }
+                    }
+                }
+            });
+        }
+    });
+
+    match failure.into_inner().unwrap_or(None) {
+        Some(error) => Err(error),
+        None => Ok(copied.into_inner()),
+    }
+}
+
+fn resolves_outside(path: &Path, root: &Path) -> bool {
+    match path.canonicalize() {
+        Ok(target) => !target.starts_with(root),
+        Err(_) => true,
+    }
+}
+
+// Inside a repository git decides, so nested `.gitignore` files,
+// `.git/info/exclude` and `core.excludesFile` all apply. A project outside
+// any repository only has its own `.gitignore`.
+enum Gitignore {
+    Repository { repo: Repository, prefix: PathBuf },
+    Rules(IgnoreRules),
+}
+
+impl Gitignore {
+    fn load(project_root: &Path) -> Gitignore {
+        let in_repo = Repository::discover(project_root).ok().and_then(|repo| {
+            let workdir = repo.workdir()?.canonicalize().ok()?;
+            let root = project_root.canonicalize().ok()?;
+            let prefix = root.strip_prefix(&workdir).ok()?.to_path_buf();
+            Some((repo, prefix))
+        });
+        match in_repo {
+            Some((repo, prefix)) => Gitignore::Repository { repo, prefix },
+            None => Gitignore::Rules(IgnoreRules::load(project_root, ".gitignore")),
+        }
+    }
+}
+
+fn is_gitignored(gitignore: Option<&Gitignore>, relative: &Path) -> bool {
+    match gitignore {
+        Some(Gitignore::Repository { repo, prefix }) => {
+            repo.is_path_ignored(prefix.join(relative)).unwrap_or(false)
+        }
+        Some(Gitignore::Rules(rules)) => {
+            rules.is_ignored_with_parents(&relative.to_string_lossy().replace('\\', "/"))
+        }
+        None => false,
+    }
+}
+
+fn write_integrity(
+    output_dir: &Path,
+    html_files: &[PathBuf],
+    warnings: &mut Vec<String>,
+) -> Result<(), String> {
+    let mut hashes: BTreeMap<String, String> = BTreeMap::new();
+    for html_file in html_files {
+        let html = fs::read_to_string(html_file).map_err(|error| error.to_string())?;
+        let base_dir = html_file.parent().unwrap_or(output_dir);
+        let (updated, found) = inject_integrity(&html, |url| {
+            let path = if let Some(rooted) = url.strip_prefix('/') {
+                output_dir.join(rooted)
+            } else {
+                base_dir.join(url)
+            };
+            let path = path.canonicalize().ok()?;
+            if !path.starts_with(output_dir) || !path.is_file() {
+                return None;
+            }
+            let key = path
+                .strip_prefix(output_dir)
+                .ok()?
+                .to_string_lossy()
+                .replace('\\', "/");
+            let hash = match hashes.get(&key) {
+                Some(hash) => hash.clone(),
+                None => {
+                    let bytes = fs::read(&path).ok()?;
+                    let hash = format!("sha384-{}", BASE64.encode(Sha384::digest(&bytes)));
+                    hashes.insert(key, hash.clone());
+                    hash
+                }
+            };
+            Some(hash)
+        });
+        for missing in found.missing {
+            warnings.push(format!(
+                "Integrity skipped, asset not found: {} ({})",
+                missing,
+                html_file.display()
+            ));
+        }
+        if found.hashed > 0 {
+            fs::write(html_file, updated).map_err(|error| write_error(html_file, error))?;
+        }
+    }
+
+    if hashes.is_empty() {
+        return Ok(());
+    }
+    let manifest = serde_json::to_string_pretty(&hashes).map_err(|error| error.to_string())?;
+    let manifest_path = output_dir.join("integrity.json");
+    fs::write(&manifest_path, format!("{}\n", manifest))
+        .map_err(|error| write_error(&manifest_path, error))
+}
+
+#[derive(Debug, Default)]
+struct IntegrityReport {
+    hashed: usize,
+    missing: Vec<String>,
+}
+
+fn inject_integrity(
+    html: &str,
+    mut hash_for: impl FnMut(&str) -> Option<String>,
+) -> (String, IntegrityReport) {
+    let mut output = String::with_capacity(html.len());
+    let mut report = IntegrityReport::default();
+    let mut cursor = 0usize;
+
+    while let Some(pos) = html[cursor..].find('<') {
+        let start = cursor + pos;
+        let end = match html[start..].find('>') {
+            Some(end) => start + end,
+            None => break,
+        };
+        let tag = &html[start..end];
+        let lower = tag.to_ascii_lowercase();
+        let url = if lower.starts_with("<script") {
+            attribute_value(tag, "src")
+        } else if lower.starts_with("<link") && lower.contains("stylesheet") {
+            attribute_value(tag, "href")
+        } else {
+            None
+        };
+
+        output.push_str(&html[cursor..start]);
+        let local = url.filter(|url| {
+            !lower.contains("integrity=")
+                && !url.contains("://")
+                && !url.starts_with("//")
+                && !url.starts_with("data:")
+        });
+        match local {
+            Some(url) => {
+                let path = url.split(['?', '#']).next().unwrap_or_default();
+                match hash_for(path) {
+                    Some(hash) => {
+                        let (body, closing) = match tag.strip_suffix('/') {
+                            Some(body) => (body.trim_end(), " /"),
+                            None => (tag, ""),
+                        };
+                        output.push_str(body);
+                        output.push_str(&format!(
+                            " integrity=\"{}\" crossorigin=\"anonymous\"{}",
+                            hash, closing
+                        ));
+                        report.hashed += 1;
+                    }
+                    None => {
+                        report.missing.push(url.to_string());
+                        output.push_str(tag);
+                    }
+                }
+            }
+            None => output.push_str(tag),
+        }
+        cursor = end;
+    }
+    output.push_str(&html[cursor..]);
+    (output, report)
+}
+
+fn attribute_value<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let lower = tag.to_ascii_lowercase();
+    let mut search = 0usize;
+    while let Some(pos) = lower[search..].find(name) {
+        let start = search + pos;
+        search = start + name.len();
+        let preceded = lower[..start].ends_with(char::is_whitespace);
+        let rest = lower[search..].trim_start();
+        if !preceded || !rest.starts_with('=') {
+            continue;
+        }
+        let value_start = tag.len() - rest.len() + 1;
+        let value = tag[value_start..].trim_start();
+        let offset = tag.len() - value.len();
+        return match value.chars().next()? {
+            quote @ ('"' | '\'') => {
+                let close = value[1..].find(quote)?;
+                Some(&tag[offset + 1..offset + 1 + close])
+            }
+            _ => {
+                let len = value
+                    .find(|c: char| c.is_whitespace() || c == '/')
+                    .unwrap_or(value.len());
+                Some(&tag[offset..offset + len])
+            }
+        };
+    }
+    None
+}
+
+// Without .nojekyll, Pages runs Jekyll and drops folders starting with `_`.
+fn write_pages_files(
+    output_dir: &Path,
+    nojekyll: bool,
+    custom_domain: Option<&str>,
+) -> Result<Vec<String>, String> {
+    let mut written = Vec::new();
+    if nojekyll {
+        fs::write(output_dir.join(".nojekyll"), "").map_err(|error| error.to_string())?;
+        written.push("Wrote .nojekyll".to_string());
+    }
+    let domain = custom_domain
+        .map(|domain| {
+            domain
+                .trim()
+                .trim_start_matches("https://")
+                .trim_start_matches("http://")
+                .trim_end_matches('/')
+        })
+        .filter(|domain| !domain.is_empty());
+    if let Some(domain) = domain {
+        fs::write(output_dir.join("CNAME"), format!("{}\n", domain))
+            .map_err(|error| error.to_string())?;
+        written.push(format!("Wrote CNAME ({})", domain));
+    }
+    Ok(written)
+}
+
+fn run_deploy(
+    request: DeployRequest,
+    cancel: &AtomicBool,
+    on_log: &dyn Fn(&str),
+) -> Result<DeployResponse, String> {
+    run_deploy_with_agent(request, cancel, on_log, &ssh_agent_running)
+}
+
+fn ssh_agent_running() -> bool {
+    !std::env::var("SSH_AUTH_SOCK")
+        .unwrap_or_default()
+        .trim()
+        .is_empty()
+}
+
+fn run_deploy_with_agent(
+    mut request: DeployRequest,
+    cancel: &AtomicBool,
+    on_log: &dyn Fn(&str),
+    has_ssh_agent: &dyn Fn() -> bool,
+) -> Result<DeployResponse, String> {
+    let project_root = PathBuf::from(&request.project_root);
+    if !project_root.exists() || !project_root.is_dir() {
+        return Err("Project root is missing".to_string());
+    }
+
+    let defaults = load_publish_defaults(&project_root)?;
+    if request.output_dir.is_none() {
+        request.output_dir = defaults.output_dir;
+    }
+    if request.remote.trim().is_empty() && request.remotes.is_empty() {
+        request.remote = defaults.remote.unwrap_or_default();
+    }
+    if request.branch.is_none() {
+        request.branch = defaults.branch;
+    }
+
+    let mut targets: Vec<&str> = Vec::new();
+    for remote in std::iter::once(&request.remote).chain(&request.remotes) {
+        let remote = remote.trim();
+        if !remote.is_empty() && !targets.contains(&remote) {
+            targets.push(remote);
+        }
+    }
+    if targets.is_empty() {
+        return Err("Deploy remote is missing".to_string());
+    }
+
+    let output_dir = resolve_output_dir(&project_root, request.output_dir.as_deref())?;
+    if !output_dir.exists() {
+        return Err("Publish directory does not exist. Run Publish first.".to_string());
+    }
+
+    let mut logs = DeployLogs::new(on_log);
+    let retention = retention_for(Some(&project_root));
+    let output_dir_canon = output_dir
+        .canonicalize()
+        .map_err(|error| error.to_string())?;
+
+    let git_dir = output_dir_canon.join(".git");
+    let repo = if git_dir.exists() {
+        Some(git::open(&output_dir_canon)?)
+    } else if request.dry_run {
+        logs.push("DRY RUN: would run git init".to_string());
+        None
+    } else {
+        logs.push("git init".to_string());
+        Some(git::init(&output_dir_canon)?)
+    };
+
+    if cancel.load(Ordering::SeqCst) {
+        return deploy_cancelled(&output_dir_canon, retention, logs);
+    }
+
+    let mut remotes = Vec::new();
+    for (index, remote) in targets.iter().enumerate() {
+        let fallback_name = match index {
+            0 => "origin".to_string(),
+            index => format!("mirror-{}", index),
+        };
+        let (remote_name, remote_url) = resolve_remote(
+            repo.as_ref(),
+            remote,
+            &fallback_name,
+            request.dry_run,
+            &mut logs,
+        )?;
+        if !is_ssh_url(&remote_url) && !remote_url.starts_with("https://") {
+            return Err("Deploy requires an SSH (git@ or ssh://) or HTTPS remote".to_string());
+        }
+        remotes.push((remote_name, remote_url));
+    }
+    if remotes.iter().any(|(_, url)| is_ssh_url(url)) && !has_ssh_agent() {
+        return Err("SSH agent not detected. Start ssh-agent first.".to_string());
+    }
+    let token = if remotes.iter().any(|(_, url)| !is_ssh_url(url)) {
+        Some(deploy_token(&project_root, request.profile.as_deref())?)
+    } else {
+        None
+    };
+    let branch = request
+        .branch
+        .clone()
+        .filter(|value| !value.trim().is_empty())
+        .unwrap_or_else(|| "main".to_string());
+    let branch = if request.gh_pages {
+        if branch != GH_PAGES_BRANCH {
+            logs.push(format!(
+                "GitHub Pages mode: deploying to {} instead of {}",
+                GH_PAGES_BRANCH, branch
+            ));
+        }
+        GH_PAGES_BRANCH.to_string()
+    } else {
+        branch
+    };
+
+    if request.dry_run {
+        return deploy_dry_run(
+            repo.as_ref(),
+            &output_dir_canon,
+            &request,
+            &branch,
+            &remotes,
+            logs,
+        );
+    }
+    let repo = repo.ok_or_else(|| "Deploy repository is missing".to_string())?;
+
+    let push = DeployPush {
+        project_root: &project_root,
+        output_dir: &output_dir_canon,
+        retention,
+        branch: &branch,
+        remotes: &remotes,
+        token: token.as_deref(),
+        push_timeout: Duration::from_secs(
+            request
+                .push_timeout_secs
+                .filter(|secs| *secs > 0)
+                .unwrap_or(DEFAULT_PUSH_TIMEOUT_SECS),
+        ),
+        cancel,
+    };
+    if let Some(target) = request
+        .rollback_to
+        .as_deref()
+        .map(str::trim)
+        .filter(|target| !target.is_empty())
+    {
+        return run_rollback(&push, &repo, target, logs);
+    }
+
+    logs.push(format!("git checkout -B {}", branch));
+    git::reset_branch(&repo, &branch)?;
+
+    if cancel.load(Ordering::SeqCst) {
+        return deploy_cancelled(&output_dir_canon, retention, logs);
+    }
+
+    if request.gh_pages {
+        for line in write_pages_files(&output_dir_canon, true, request.custom_domain.as_deref())? {
+            logs.push(line);
+        }
+    }
+
+    logs.push("git add -A".to_string());
+    git::add_all(&repo)?;
+
+    let status = git::status(&repo)?;
+    if status.is_empty() {
+        append_log(
+            &output_dir_canon.join(".deploy.log"),
+            retention,
+            "DEPLOY",
+            "No changes to deploy",
+        )?;
+        return Ok(DeployResponse {
+            ok: true,
+            summary: "No changes to deploy".to_string(),
+            logs: logs.lines,
+        });
+    }
+
+    if cancel.load(Ordering::SeqCst) {
+        return deploy_cancelled(&output_dir_canon, retention, logs);
+    }
+
+    let date = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let custom_message = request
+        .message
+        .as_deref()
+        .filter(|message| !message.trim().is_empty());
+    let template = request
+        .message_template
+        .as_deref()
+        .filter(|template| !template.trim().is_empty());
+    let message = match (custom_message, template) {
+        (Some(message), _) => custom_deploy_message(message, &date),
+        (None, Some(template)) => render_deploy_message(template, &status, &date),
+        (None, None) => format!("Publish snapshot @ {}", date),
+    };
+    logs.push("git commit".to_string());
+    git::commit(&repo, &message, &[], None, None)?;
+
+    let tag = match request
+        .tag
+        .as_deref()
+        .filter(|template| !template.trim().is_empty())
+    {
+        Some(template) => {
+            let day = Local::now().format("%Y-%m-%d").to_string();
+            let name = deploy_tag_name(&repo, template, &day, request.fail_on_existing_tag)?;
+            logs.push(format!("git tag -a {}", name));
+            git::annotated_tag(&repo, &name, &message)?;
+            Some(name)
+        }
+        None => None,
+    };
+
+    let summary = |pushed: &str| {
+        let mut summary = format!("Deployed to {} ({})", pushed, branch);
+        if let Some(tag) = &tag {
+            summary.push_str(&format!(", tagged {}", tag));
+        }
+        summary
+    };
+    push_deploy(&push, request.force, tag.is_some(), &summary, logs)
+}
+
+struct DeployPush<'a> {
+    project_root: &'a Path,
+    output_dir: &'a Path,
+    retention: (u64, usize),
+    branch: &'a str,
+    remotes: &'a [(String, String)],
+    token: Option<&'a str>,
+    push_timeout: Duration,
+    cancel: &'a AtomicBool,
+}
+
+// Rewinding the branch always needs a forced push.
+fn run_rollback(
+    push: &DeployPush,
+    repo: &Repository,
+    target: &str,
+    mut logs: DeployLogs,
+) -> Result<DeployResponse, String> {
+    let (before, after) = rollback_commits(repo, push.remotes, push.branch, target)?;
+    logs.push(format!(
+        "Rolling back {} from {} to {}",
+        push.branch, before, after
+    ));
+    git::reset_branch_hard(repo, push.branch, after)?;
+
+    let summary = |pushed: &str| {
+        format!(
+            "Rolled back {} to {} on {}",
+            push.branch,
+            short_sha(after),
+            pushed
+        )
+    };
+    push_deploy(push, true, false, &summary, logs)
+}
+
+// Pushes the branch to every remote. Deploys push through the git CLI rather
+// than git::push: it gives us --force-with-lease, progress lines to stream,
+// and a child process that the timeout and cancel can kill mid-push.
+fn push_deploy(
+    push: &DeployPush,
+    force: bool,
+    tags: bool,
+    summary: &dyn Fn(&str) -> String,
+    mut logs: DeployLogs,
+) -> Result<DeployResponse, String> {
+    let DeployPush {
+        project_root,
+        output_dir,
+        retention,
+        branch,
+        remotes,
+        token,
+        push_timeout,
+        cancel,
+    } = *push;
+    if cancel.load(Ordering::SeqCst) {
+        return deploy_cancelled(output_dir, retention, logs);
+    }
+
+    let mut pushed = Vec::new();
+    let mut failed = Vec::new();
+    for (remote_name, remote_url) in remotes {
+        if cancel.load(Ordering::SeqCst) {
+            return deploy_cancelled(output_dir, retention, logs);
+        }
+        // The lease is checked against the remote-tracking ref from the last
+        // deploy, so a push someone else made since then is still refused.
+        let mut push_args = vec!["push", "--progress", "-u"];
+        if force {
+            logs.push(format!(
+                "Force pushing {} to {} with --force-with-lease",
+                branch, remote_name
+            ));
+            push_args.push("--force-with-lease");
+        }
+        if tags {
+            push_args.push("--tags");
+        }
+        push_args.push(remote_name.as_str());
+        push_args.push(branch);
+        let remote_token = if is_ssh_url(remote_url) { None } else { token };
+        match run_git_command_watched(
+            output_dir,
+            &mut logs,
+            &push_args,
+            remote_token,
+            push_timeout,
+            cancel,
+        ) {
+            Ok(_) => {
+                append_log(
+                    &output_dir.join(".deploy.log"),
+                    retention,
+                    "DEPLOY",
+                    format!(
+                        "{} to {} ({})",
+                        if force { "Force-pushed" } else { "Pushed" },
+                        remote_name,
+                        branch
+                    )
+                    .as_str(),
+                )?;
+                pushed.push(remote_name.as_str());
+            }
+            Err(error) if error == "deploy_cancelled" => {
+                return deploy_cancelled(output_dir, retention, logs);
+            }
+            Err(error) => {
+                logs.push(format!("Push to {} failed: {}", remote_name, error));
+                failed.push((remote_name.as_str(), error));
+            }
+        }
+    }
+
+    // Mirrors are best effort: the deploy only fails when no remote took it.
+    if pushed.is_empty() {
+        let remote_names = remotes
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        return Err(match failed.pop() {
+            Some((_, error)) if remotes.len() == 1 => error,
+            _ => format!("Push failed for every remote: {}", remote_names),
+        });
+    }
+
+    let mut summary = summary(&pushed.join(", "));
+    if !failed.is_empty() {
+        let failed_names: Vec<&str> = failed.iter().map(|(name, _)| *name).collect();
+        summary.push_str(&format!("; push failed for {}", failed_names.join(", ")));
+    }
+    if let Ok(config) = load_config(project_root) {
+        if let Some(warning) = notify_success(
+            project_root,
+            &config,
+            None,
+            &output_dir.to_string_lossy(),
+            &summary,
+        ) {
+            logs.push(format!("Notification failed: {}", warning));
+        }
+    }
+
+    Ok(DeployResponse {
+        ok: true,
+        summary,
+        logs: logs.lines,
+    })
+}
+
+fn deploy_dry_run(
+    repo: Option<&Repository>,
+    output_dir: &Path,
+    request: &DeployRequest,
+    branch: &str,
+    remotes: &[(String, String)],
+    mut logs: DeployLogs,
+) -> Result<DeployResponse, String> {
+    let remote_names = remotes
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let rollback = request
+        .rollback_to
+        .as_deref()
+        .map(str::trim)
+        .filter(|target| !target.is_empty());
+    if let Some(target) = rollback {
+        let repo = repo.ok_or_else(|| format!("Rollback target not found: {}", target))?;
+        let (before, after) = rollback_commits(repo, remotes, branch, target)?;
+        logs.push(format!(
+            "Rolling back {} from {} to {}",
+            branch, before, after
+        ));
+        for (remote_name, remote_url) in remotes {
+            logs.push(format!(
+                "DRY RUN: would force push {} to {} ({})",
+                branch, remote_name, remote_url
+            ));
+        }
+        return Ok(DeployResponse {
+            ok: true,
+            summary: format!(
+                "Dry run: {} would be rolled back to {} on {}",
+                branch,
+                short_sha(after),
+                remote_names
+            ),
+            logs: logs.lines,
+        });
+    }
+
+    logs.push(format!("DRY RUN: would check out {}", branch));
+    let status = git::pending_changes(output_dir, repo)?;
+    logs.push("git status --porcelain".to_string());
+    for line in git::format_status(&status).lines() {
+        logs.push(line.to_string());
+    }
+    logs.push(format!(
+        "DRY RUN: would commit {} change(s) on {}",
+        status.len(),
+        branch
+    ));
+    for (remote_name, remote_url) in remotes {
+        logs.push(format!(
+            "DRY RUN: would push {} to {} ({})",
+            branch, remote_name, remote_url
+        ));
+    }
+    Ok(DeployResponse {
+        ok: true,
+        summary: format!(
+            "Dry run: {} change(s) would be deployed to {} ({})",
+            status.len(),
+            remote_names,
+            branch
+        ),
+        logs: logs.lines,
+    })
+}
+
+// The branch tip and the commit a rollback to `target` would reset it to.
+// `previous` is the parent of what the first remote last received, read from
+// its remote-tracking ref, so an unpushed local commit is not mistaken for
+// the live deploy.
+fn rollback_commits(
+    repo: &Repository,
+    remotes: &[(String, String)],
+    branch: &str,
+    target: &str,
+) -> Result<(Oid, Oid), String> {
+    let before = git::branch_tip(repo, branch)?;
+    let spec = if target == "previous" {
+        let remote = remotes
+            .first()
+            .map(|(name, _)| name.as_str())
+            .unwrap_or("origin");
+        let deployed = git::resolve_commit(repo, &format!("refs/remotes/{}/{}", remote, branch))
+            .map_err(|_| format!("No previous deploy of {} to {} found", branch, remote))?;
+        format!("{}~1", deployed)
+    } else {
+        target.to_string()
+    };
+    let after = git::resolve_commit(repo, &spec)
+        .map_err(|_| format!("Rollback target not found: {}", target))?;
+    Ok((before, after))
+}
+
+const DEPLOY_MESSAGE_MAX_FILES: usize = 20;
+
+fn render_deploy_message(template: &str, status: &[StatusEntry], date: &str) -> String {
+    let files: Vec<&str> = status.iter().map(|entry| entry.path.as_str()).collect();
+    let mut listed = files
+        .iter()
+        .take(DEPLOY_MESSAGE_MAX_FILES)
+        .copied()
+        .collect::<Vec<_>>()
+        .join(", ");
+    if files.len() > DEPLOY_MESSAGE_MAX_FILES {
+        listed.push_str(&format!(
+            " and {} more",
+            files.len() - DEPLOY_MESSAGE_MAX_FILES
+        ));
+    }
+    template
+        .replace("{count}", &files.len().to_string())
+        .replace("{files}", &listed)
+        .replace("{date}", date)
+}
+
+fn deploy_tag_name(
+    repo: &Repository,
+    template: &str,
+    date: &str,
+    fail_if_exists: bool,
+) -> Result<String, String> {
+    let name = template.trim().replace("{date}", date);
+    if !git::is_valid_tag_name(&name) {
+        return Err(format!("Invalid git tag name: {}", name));
+    }
+    if !git::tag_exists(repo, &name) {
+        return Ok(name);
+    }
+    if fail_if_exists {
+        return Err(format!("Git tag already exists: {}", name));
+    }
+    let mut suffix = 2;
+    loop {
+        let candidate = format!("{}-{}", name, suffix);
+        if !git::tag_exists(repo, &candidate) {
+            return Ok(candidate);
+        }
+        suffix += 1;
+    }
+}
+
+fn short_sha(oid: Oid) -> String {
+    oid.to_string().chars().take(7).collect()
+}
+
+fn custom_deploy_message(message: &str, date: &str) -> String {
+    if message.contains("{date}") {
+        message.replace("{date}", date)
+    } else {
+        format!("{} @ {}", message.trim_end(), date)
+    }
+}
+
+struct DeployLogs<'a> {
+    lines: Vec<String>,
+    emit: &'a dyn Fn(&str),
+}
+
+impl<'a> DeployLogs<'a> {
+    fn new(emit: &'a dyn Fn(&str)) -> Self {
+        Self {
+            lines: Vec::new(),
+            emit,
+        }
+    }
+
+    fn push(&mut self, line: String) {
+        (self.emit)(&line);
+        self.lines.push(line);
+    }
+}
+
+fn deploy_cancelled(
+    output_dir: &Path,
+    retention: (u64, usize),
+    logs: DeployLogs,
+) -> Result<DeployResponse, String> {
+    append_log(
+        &output_dir.join(".deploy.log"),
+        retention,
+        "DEPLOY",
+        "Deploy cancelled",
+    )?;
+    Ok(DeployResponse {
+        ok: false,
+        summary: "Deploy cancelled".to_string(),
+        logs: logs.lines,
+    })
+}
+
+fn load_publish_defaults(project_root: &Path) -> Result<PublishConfig, String> {
+    match load_config(project_root) {
+        Ok(config) => Ok(config.publish.unwrap_or_default()),
+        Err(error) if matches!(error.code, ExportErrorCode::ConfigMissing) => {
+            Ok(PublishConfig::default())
+        }
+        Err(error) => Err(match error.detail {
+            Some(detail) => format!("{}: {}", error.message, detail),
+            None => error.message,
+        }),
+    }
+}
+
+pub(crate) fn publish_output_dir(project_root: &Path) -> Result<PathBuf, String> {
+    let defaults = load_publish_defaults(project_root)?;
+    resolve_output_dir(project_root, defaults.output_dir.as_deref())
+}
+
+fn resolve_output_dir(project_root: &Path, output_dir: Option<&str>) -> Result<PathBuf, String> {
+    let value = output_dir.unwrap_or("_publish").trim();
+    if value.is_empty() {
+        return Err("Publish directory cannot be empty".to_string());
+    }
+    let path = PathBuf::from(value);
+    if path.is_absolute() {
+        Ok(path)
+    } else {
+        Ok(project_root.join(path))
+    }
+}
+
+pub(crate) fn backup_dir_for(output_dir: &Path) -> Result<PathBuf, String> {
+    suffixed_dir(output_dir, ".bak")
+}
+
+// Appends to the whole folder name, so `site.v2` becomes `site.v2.bak` rather
+// than having its "extension" replaced.
+fn suffixed_dir(output_dir: &Path, suffix: &str) -> Result<PathBuf, String> {
+    let name = output_dir
+        .file_name()
+        .ok_or_else(|| "Publish directory has no name".to_string())?;
+    let mut suffixed = name.to_os_string();
+    suffixed.push(suffix);
+    Ok(output_dir.with_file_name(suffixed))
+}
+
+fn ensure_contained(project_root: &Path, path: &Path) -> Result<(), String> {
+    let root = project_root
+        .canonicalize()
+        .map_err(|error| error.to_string())?;
+    let parent = path
+        .parent()
+        .ok_or_else(|| "Publish directory must stay inside the project root".to_string())?
+        .canonicalize()
+        .map_err(|error| error.to_string())?;
+    let name = path
+        .file_name()
+        .ok_or_else(|| "Publish directory must stay inside the project root".to_string())?;
+    let resolved = parent.join(name);
+    if resolved == root || !resolved.starts_with(&root) {
+        return Err("Publish directory must stay inside the project root".to_string());
+    }
+    Ok(())
+}
+
+// The deploy repository lives inside the output directory, so it follows the
+// live output instead of being archived with the backup.
+fn rotate_backup(project_root: &Path, output_dir: &Path) -> Result<PathBuf, String> {
+    let backup_dir = backup_dir_for(output_dir)?;
+    ensure_contained(project_root, output_dir)?;
+    ensure_contained(project_root, &backup_dir)?;
+    if backup_dir.exists() {
+        fs::remove_dir_all(&backup_dir).map_err(|error| write_error(&backup_dir, error))?;
+    }
+    fs::rename(output_dir, &backup_dir)
+        .map_err(|error| transfer_error(output_dir, &backup_dir, error))?;
+    fs::create_dir_all(output_dir).map_err(|error| write_error(output_dir, error))?;
+    move_git_dir(&backup_dir, output_dir)?;
+    Ok(backup_dir)
+}
+
+fn move_git_dir(from: &Path, to: &Path) -> Result<(), String> {
+    let source = from.join(".git");
+    let target = to.join(".git");
+    if source.exists() && !target.exists() {
+        fs::rename(&source, &target).map_err(|error| transfer_error(&source, &target, error))?;
+    }
+    Ok(())
+}
+
+fn resolve_remote(
+    repo: Option<&Repository>,
+    remote: &str,
+    url_remote_name: &str,
+    dry_run: bool,
+    logs: &mut DeployLogs,
+) -> Result<(String, String), String> {
+    let trimmed = remote.trim();
+    let looks_like_url = trimmed.contains("://") || trimmed.starts_with("git@");
+    if looks_like_url {
+        let remote_name = url_remote_name.to_string();
+        match repo.filter(|_| !dry_run) {
+            Some(repo) => {
+                logs.push(format!("git remote set-url {} {}", remote_name, trimmed));
+                git::set_remote(repo, &remote_name, trimmed)?;
+            }
+            None => logs.push(format!(
+                "DRY RUN: would run git remote set-url {} {}",
+                remote_name, trimmed
+            )),
+        }
+        return Ok((remote_name, trimmed.to_string()));
+    }
+
+    let repo = repo.ok_or_else(|| format!("remote '{}' not found", trimmed))?;
+    let url = git::remote_url(repo, trimmed)?;
+    Ok((trimmed.to_string(), url.trim().to_string()))
+}
+
+fn is_ssh_url(url: &str) -> bool {
+    url.starts_with("git@") || url.starts_with("ssh://")
+}
+
+fn deploy_token(project_root: &Path, profile: Option<&str>) -> Result<String, String> {
+    match lookup_credential(
+        &project_root.to_string_lossy(),
+        CredentialTarget::Git,
+        profile,
+        CredentialKind::Token,
+    ) {
+        Ok(Some(token)) => Ok(token),
+        Ok(None) => Err("Git token missing (set in app); HTTPS remotes need a token".to_string()),
+        Err(error) if error == CREDENTIAL_STORE_LOCKED => {
+            Err("Credential storage did not respond (is the keyring locked?)".to_string())
+        }
+        Err(error) => Err(format!("Unable to access credential storage: {}", error)),
+    }
+}
+
+fn run_git_command_watched(
+    repo_path: &Path,
+    logs: &mut DeployLogs,
+    args: &[&str],
+    token: Option<&str>,
+    timeout: Duration,
+    cancel: &AtomicBool,
+) -> Result<String, String> {
+    let mut command = Command::new("git");
+    if let Some(token) = token {
+        command
+            .args([
+                "-c",
+                "credential.helper=",
+                "-c",
+                git::GIT_TOKEN_CREDENTIAL_HELPER,
+            ])
+            .env("ERNEST_GIT_TOKEN", token.trim());
+    }
+    let mut child = command
+        .args(args)
+        .current_dir(repo_path)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(git::git_spawn_error)?;
+    logs.push(format!("git {}", args.join(" ")));
+
+    let stdout_reader = child.stdout.take().map(|mut pipe| {
+        thread::spawn(move || {
+            let mut buffer = String::new();
+            let _ = pipe.read_to_string(&mut buffer);
+            buffer
+        })
+    });
+    // Progress lines are forwarded while the command runs; push progress is
+    // redrawn with `\r`, so that counts as a line break too.
+    let (progress_tx, progress_rx) = mpsc::channel();
+    let stderr_reader = child.stderr.take().map(|mut pipe| {
+        thread::spawn(move || {
+            let mut output = String::new();
+            let mut line = Vec::new();
+            let mut chunk = [0u8; 1024];
+            while let Ok(read) = pipe.read(&mut chunk) {
+                if read == 0 {
+                    break;
+                }
+                for &byte in &chunk[..read] {
+                    if byte != b'\r' && byte != b'\n' {
+                        line.push(byte);
+                        continue;
+                    }
+                    let text = String::from_utf8_lossy(&line).to_string();
+                    if byte == b'\n' {
+                        output.push_str(&text);
+                        output.push('\n');
+                    }
+                    if !text.trim().is_empty() {
+                        let _ = progress_tx.send(text.trim().to_string());
+                    }
+                    line.clear();
+                }
+            }
+            output.push_str(&String::from_utf8_lossy(&line));
+            output
+        })
+    });
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        for line in progress_rx.try_iter() {
+            (logs.emit)(&line);
+        }
+        if let Some(status) = child.try_wait().map_err(|error| error.to_string())? {
+            break status;
+        }
+        if cancel.load(Ordering::SeqCst) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err("deploy_cancelled".to_string());
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(format!(
+                "git {} timed out after {}s",
+                args.first().unwrap_or(&""),
+                timeout.as_secs()
+            ));
+        }
+        thread::sleep(Duration::from_millis(100));
+    };
+
+    let stdout = stdout_reader
+        .and_then(|reader| reader.join().ok())
+        .unwrap_or_default();
+    let stderr = stderr_reader
+        .and_then(|reader| reader.join().ok())
+        .unwrap_or_default();
+    for line in progress_rx.try_iter() {
+        (logs.emit)(&line);
+    }
+
+    if status.success() {
+        Ok(format!("{}{}", stdout, stderr))
+    } else if stderr.trim().is_empty() {
+        Err(stdout)
+    } else {
+        Err(format!("{}{}", stdout, stderr))
+    }
+}
+
+fn append_log(
+    path: &Path,
+    retention: (u64, usize),
+    label: &str,
+    message: &str,
+) -> Result<(), String> {
+    let (max_bytes, keep) = retention;
+    rotate_if_needed(path, max_bytes, keep).map_err(|error| write_error(path, error))?;
+    let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let entry = format!("{} [{}] {}\n", timestamp, label, message);
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|error| write_error(path, error))?;
+    file.write_all(entry.as_bytes())
+        .map_err(|error| write_error(path, error))?;
+    Ok(())
+}
+
+fn write_error(path: &Path, error: std::io::Error) -> String {
+    match error.kind() {
+        std::io::ErrorKind::PermissionDenied => format!(
+            "Cannot write to project directory (permission denied): {}",
+            path.display()
+        ),
+        std::io::ErrorKind::NotFound => format!(
+            "Directory does not exist: {}",
+            path.parent().unwrap_or(path).display()
+        ),
+        _ => format!("{}: {}", error, path.display()),
+    }
+}
+
+fn read_error(path: &Path, error: std::io::Error) -> String {
+    match error.kind() {
+        std::io::ErrorKind::PermissionDenied => {
+            format!("Cannot read file (permission denied): {}", path.display())
+        }
+        std::io::ErrorKind::NotFound => format!("File does not exist: {}", path.display()),
+        _ => format!("{}: {}", error, path.display()),
+    }
+}
+
+fn transfer_error(source: &Path, target: &Path, error: std::io::Error) -> String {
+    if fs::File::open(source).is_err() {
+        read_error(source, error)
+    } else {
+        write_error(target, error)
+    }
+}
+
+pub(crate) struct AssetRef {
+    pub(crate) target: String,
+    pub(crate) line: usize,
+}
+
+fn line_at(content: &str, offset: usize) -> usize {
+    content[..offset].matches('\n').count() + 1
+}
+
+pub(crate) fn extract_local_assets(content: &str) -> Vec<AssetRef> {
+    let mut results = Vec::new();
+    let mut cursor = 0usize;
+    while let Some(pos) = content[cursor..].find("](") {
+        let start = cursor + pos + 2;
+        if let Some(end) = content[start..].find(')') {
+            let raw = &content[start..start + end];
+            let trimmed = raw.trim();
+            let target = trimmed
+                .trim_matches('<')
+                .trim_matches('>')
+                .split_whitespace()
+                .next()
+                .unwrap_or("")
+                .trim();
+            if is_local_target(target) {
+                results.push((start, target.to_string()));
+            }
+            cursor = start + end + 1;
+        } else {
+            break;
+        }
+    }
+    results.extend(reference_definitions(content));
+    for attribute in ["src=", "href="] {
+        results.extend(html_attribute_values(content, attribute));
+    }
+    results.sort_by_key(|(offset, _)| *offset);
+    results
+        .into_iter()
+        .map(|(offset, target)| AssetRef {
+            target,
+            line: line_at(content, offset),
+        })
+        .collect()
+}
+
+// Targets of link reference definitions such as `[logo]: <img/logo.png> "Logo"`.
+// Footnotes (`[^1]: text`) share the syntax and are skipped.
+fn reference_definitions(content: &str) -> Vec<(usize, String)> {
+    let mut values = Vec::new();
+    let mut offset = 0usize;
+    for line in content.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+        let indent = line.len() - line.trim_start_matches(' ').len();
+        let rest = &line[indent..];
+        if indent > 3 || !rest.starts_with('[') || rest.starts_with("[^") {
+            continue;
+        }
+        let after = match rest.find("]:") {
+            Some(end) if end > 1 => &rest[end + 2..],
+            _ => continue,
+        };
+        let value = after.trim_start();
+        let target = match value.strip_prefix('<') {
+            Some(bracketed) => bracketed.split('>').next().unwrap_or(""),
+            None => value.split_whitespace().next().unwrap_or(""),
+        };
+        if is_local_target(target) {
+            let target_start =
+                line_start + (line.len() - value.len()) + usize::from(value.starts_with('<'));
+            values.push((target_start, target.to_string()));
+        }
+    }
+    values
+}
+
+// Quoted values of `attribute` wherever it starts a new attribute, so
+// `data-src=` or `srcset=` don't count.
+fn html_attribute_values(content: &str, attribute: &str) -> Vec<(usize, String)> {
+    let mut values = Vec::new();
+    let mut cursor = 0usize;
+    while let Some(pos) = content[cursor..].find(attribute) {
+        let start = cursor + pos;
+        cursor = start + attribute.len();
+        let at_boundary = content[..start]
+            .chars()
+            .next_back()
+            .is_some_and(char::is_whitespace);
+        let quote = match content[cursor..].chars().next() {
+            Some(quote @ ('"' | '\'')) => quote,
+            _ => continue,
+        };
+        let value_start = cursor + 1;
+        let end = match content[value_start..].find(quote) {
+            Some(end) => end,
+            None => break,
+        };
+        let target = content[value_start..value_start + end].trim();
+        if at_boundary && is_local_target(target) {
+            values.push((value_start, target.to_string()));
+        }
+        cursor = value_start + end + 1;
+    }
+    values
+}
+
+fn is_local_target(target: &str) -> bool {
+    !target.is_empty()
+        && !target.starts_with("http://")
+        && !target.starts_with("https://")
+        && !target.starts_with("mailto:")
+        && !target.starts_with("tel:")
+        && !target.starts_with("data:")
+        && !target.starts_with("//")
+        && !target.starts_with('#')
+}
+
+fn render_markdown_html(content: &str, fallback_title: &str) -> String {
+    let (body, title) = match split_frontmatter(content) {
+        Some(frontmatter) => (frontmatter.body, scalar_value(&frontmatter, "title")),
+        None => (strip_bom(content), None),
+    };
+    let title = title.unwrap_or_else(|| fallback_title.to_string());
+
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_FOOTNOTES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    let events = Parser::new_ext(body, options).map(|event| match event {
+        Event::Start(Tag::Link {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }) => Event::Start(Tag::Link {
+            link_type,
+            dest_url: html_link_target(&dest_url)
+                .map(CowStr::from)
+                .unwrap_or(dest_url),
+            title,
+            id,
+        }),
+        event => event,
+    });
+    let mut rendered = String::new();
+    html::push_html(&mut rendered, events);
+
+    let mut escaped_title = String::new();
+    html::push_html(
+        &mut escaped_title,
+        std::iter::once(Event::Text(title.into())),
+    );
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escaped_title, rendered
+    )
+}
+
+fn html_link_target(target: &str) -> Option<String> {
+    if !is_local_target(target) {
+        return None;
+    }
+    let split = target.find(['#', '?']).unwrap_or(target.len());
+    let (path, suffix) = target.split_at(split);
+    if !has_extension(Path::new(path), DOCUMENT_EXTENSIONS) {
+        return None;
+    }
+    let stem = &path[..path.rfind('.')?];
+    Some(format!("{}.html{}", stem, suffix))
+}
+
+const DEFAULT_FRONTMATTER_ASSET_KEYS: &[&str] = &["cover", "image", "thumbnail"];
+
+fn frontmatter_asset_keys(defaults: &PublishConfig) -> Vec<String> {
+    match &defaults.frontmatter_asset_keys {
+        Some(keys) => keys.clone(),
+        None => DEFAULT_FRONTMATTER_ASSET_KEYS
+            .iter()
+            .map(|key| key.to_string())
+            .collect(),
+    }
+}
+
+pub(crate) fn extract_frontmatter_assets(content: &str, keys: &[String]) -> Vec<AssetRef> {
+    let frontmatter = match split_frontmatter(content) {
+        Some(frontmatter) => frontmatter,
+        None => return Vec::new(),
+    };
+    keys.iter()
+        .flat_map(|key| string_values(&frontmatter, key))
+        .filter(|target| is_local_target(target))
+        .map(|target| AssetRef {
+            line: line_at(content, content.find(&target).unwrap_or(0)),
+            target,
+        })
+        .collect()
+}
+
+fn normalize_base_path(base_path: Option<&str>) -> Option<String> {
+    let trimmed = base_path?.trim().trim_matches('/');
+    if trimmed.is_empty() {
+        return None;
+    }
+    Some(format!("/{}", trimmed))
+}
+
+fn rewrite_base_path(content: &str, base_path: &str) -> (String, usize) {
+    let mut output = String::with_capacity(content.len());
+    let mut count = 0usize;
+    let mut cursor = 0usize;
+    while let Some(pos) = content[cursor..].find("](") {
+        let start = cursor + pos + 2;
+        output.push_str(&content[cursor..start]);
+        let target_start = start + (content[start..].len() - content[start..].trim_start().len());
+        let target_start = if content[target_start..].starts_with('<') {
+            target_start + 1
+        } else {
+            target_start
+        };
+        output.push_str(&content[start..target_start]);
+        // Source links are always written from the site root, so a link that
+        // happens to start with the base path still gets it.
+        let rest = &content[target_start..];
+        if rest.starts_with('/') && !rest.starts_with("//") {
+            output.push_str(base_path);
+            count += 1;
+        }
+        cursor = target_start;
+    }
+    output.push_str(&content[cursor..]);
+    (output, count)
+}
+
+pub(crate) fn resolve_asset_path(
+    project_root: &Path,
+    file_path: &Path,
+    asset: &str,
+) -> Option<PathBuf> {
+    let trimmed = asset.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+    if trimmed.starts_with('/') {
+        return Some(project_root.join(trimmed.trim_start_matches('/')));
+    }
+    let parent = file_path.parent()?;
+    Some(parent.join(trimmed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("ernest-test-{}-{}", name, suffix));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn publish_jobs_only_cancel_their_own_kind() {
+        let jobs = PublishJobs::default();
+        let cancel = Arc::new(AtomicBool::new(false));
+        jobs.insert("deploy-1".to_string(), JobKind::Deploy, cancel.clone());
+
+        assert!(jobs.cancel("deploy-1", JobKind::Publish).is_err());
+        assert!(!cancel.load(Ordering::SeqCst));
+        assert!(jobs.cancel("deploy-1", JobKind::Deploy).is_ok());
+        assert!(cancel.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn resolve_output_dir_joins_relative() {
+        let root = PathBuf::from("/tmp/project-root");
+        let result = resolve_output_dir(&root, Some("_publish")).unwrap();
+        assert_eq!(result, root.join("_publish"));
+    }
+
+    #[test]
+    fn publish_project_copies_files() {
+        let project_root = temp_dir("publish");
+        let file_path = project_root.join("note.md");
+        fs::write(&file_path, "---\ntitle: Hello\n---\nBody").unwrap();
+
+        let response = publish_project(PublishRequest {
+            project_root: project_root.to_string_lossy().to_string(),
+            files: vec![file_path.to_string_lossy().to_string()],
+            output_dir: Some("_publish".into()),
+            ..Default::default()
+        })
+        .expect("publish should succeed");
+
+        assert!(response.ok);
+        assert!(response.summary.contains("Published"));
+        let published = project_root.join("_publish/note.md");
+        assert!(published.exists(), "expected published file to exist");
+
+        let _ = fs::remove_dir_all(&project_root);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn publish_project_skips_symlinks_escaping_the_project() {
+        let project_root = temp_dir("publish-symlink");
+        let outside = temp_dir("publish-symlink-outside");
+        fs::write(outside.join("secret.txt"), "secret").unwrap();
+        fs::create_dir_all(project_root.join("img")).unwrap();
+        fs::write(project_root.join("img/real.png"), "png").unwrap();
+        std::os::unix::fs::symlink(
+            outside.join("secret.txt"),
+            project_root.join("img/leak.png"),
+        )
+        .unwrap();
+        std::os::unix::fs::symlink(
+            project_root.join("img/real.png"),
+            project_root.join("alias.png"),
+        )
+        .unwrap();
+        fs::write(
+            project_root.join("note.md"),
+            "![l](img/leak.png) ![a](alias.png)",
+        )
+        .unwrap();
+
+        let response = publish_project(PublishRequest {
+            project_root: project_root.to_string_lossy().to_string(),
+            files: vec![project_root.join("note.md").to_string_lossy().to_string()],
+            ..Default::default()
+        })
+        .expect("publish should succeed");
+
+        assert!(response.ok);
+        assert_eq!(response.warnings.len(), 1, "{:?}", response.warnings);
+        assert!(response.warnings[0]
+            .starts_with("Skipped symlinked asset outside project: img/leak.png"));
+        assert!(!project_root.join("_publish/img/leak.png").exists());
+        assert!(project_root.join("_publish/alias.png").exists());
+
+        let _ = fs::remove_dir_all(&project_root);
+        let _ = fs::remove_dir_all(&outside);
+    }
+
+    #[test]
+    fn run_publish_reports_each_copied_path() {
+        let project_root = temp_dir("publish-progress");
+        fs::create_dir_all(project_root.join("img")).unwrap();
+        fs::write(project_root.join("a.md"), "![x](img/x.png)").unwrap();
+        fs::write(project_root.join("b.md"), "Body").unwrap();
+        fs::write(project_root.join("img/x.png"), "png").unwrap();
+        let seen = Mutex::new(Vec::new());
+
+        run_publish(
+            PublishRequest {
+                project_root: project_root.to_string_lossy().to_string(),
+                files: vec![
+                    project_root.join("a.md").to_string_lossy().to_string(),
+                    project_root.join("b.md").to_string_lossy().to_string(),
+                ],
+                ..Default::default()
+            },
+            &AtomicBool::new(false),
+            &|index, count, path| {
+                seen.lock()
+                    .unwrap()
+                    .push((index, count, path.to_string_lossy().to_string()))
+            },
+        )
+        .expect("publish should succeed");
+
+        assert_eq!(
+            seen.into_inner().unwrap(),
+            vec![
+                (0, 2, "a.md".to_string()),
+                (0, 2, "img/x.png".to_string()),
+                (1, 2, "b.md".to_string()),
+            ]
+        );
+
+        let _ = fs::remove_dir_all(&project_root);
+    }
+
+    #[test]
+    fn publish_project_strips_bom() {
+        let project_root = temp_dir("publish-bom");
+        let file_path = project_root.join("note.md");
+        fs::write(&file_path, "\u{feff}---\ntitle: Hello\n---\nBody").unwrap();
+        fs::write(
+            project_root.join(".export.toml"),
+            "\u{feff}version = 1\n\n[publish]\noutput_dir = \"_site\"\n",
+        )
+        .unwrap();
+
+        let response = publish_project(PublishRequest {
+            project_root: project_root.to_string_lossy().to_string(),
+            files: vec![file_path.to_string_lossy().to_string()],
+            ..Default::default()
+        })
+        .expect("publish should succeed");
+
+        assert!(response.ok);
+        let published = fs::read_to_string(project_root.join("_site/note.md")).unwrap();
+        assert_eq!(published, "---\ntitle: Hello\n---\nBody");
+
+        let _ = fs::remove_dir_all(&project_root);
+    }
+
+    #[test]
+    fn publish_project_flattens_documents_from_config() {
+        let project_root = temp_dir("publish-flatten");
+        fs::create_dir_all(project_root.join("posts/img")).unwrap();
+        fs::create_dir_all(project_root.join("drafts")).unwrap();
+        fs::write(project_root.join("posts/a.md"), "![x](img/x.png)\n").unwrap();
+        fs::write(project_root.join("posts/img/x.png"), "png").unwrap();
+        fs::write(project_root.join("drafts/a.md"), "draft\n").unwrap();
+        fs::write(
+            project_root.join(".export.toml"),
+            "version = 1\n\n[publish]\nflatten = true\n",
+        )
+        .unwrap();
+
+        let response = publish_project(PublishRequest {
+            project_root: project_root.to_string_lossy().to_string(),
+            files: vec![
+                project_root
+                    .join("posts/a.md")
+                    .to_string_lossy()
+                    .to_string(),
+                project_root
+                    .join("drafts/a.md")
+                    .to_string_lossy()
+                    .to_string(),
+            ],
+            ..Default::default()
+        })
+        .expect("publish should succeed");
+
+        assert!(response.ok);
+        assert_eq!(
+            fs::read_to_string(project_root.join("_publish/a.md")).unwrap(),
+            "![x](img/x.png)\n"
+        );
+        assert!(project_root.join("_publish/img/x.png").exists());
+        assert!(!project_root.join("_publish/posts").exists());
+        assert!(response
+            .warnings
+            .iter()
+            .any(|warning| warning.contains("flattened name collides")));
+
+        let _ = fs::remove_dir_all(&project_root);
+    }
+
+    #[test]
+    fn publish_project_reports_an_unwritable_output_as_a_failed_response() {
+        let project_root = temp_dir("publish-unwritable");
+        let file_path = project_root.join("note.md");
+        fs::write(&file_path, "Body").unwrap();
+        fs::write(project_root.join("_publish"), "not a folder").unwrap();
+
+        let response = publish_project(PublishRequest {
+            project_root: project_root.to_string_lossy().to_string(),
+            files: vec![file_path.to_string_lossy().to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(!response.ok);
+        assert!(matches!(
+            response.error.unwrap().code,
+            PublishErrorCode::OutputNotWritable
+        ));
+
+        let _ = fs::remove_dir_all(&project_root);
+    }
+
+    #[test]
+    fn publish_project_reports_an_invalid_publish_config() {
+        let project_root = temp_dir("publish-bad-config");
+        let file_path = project_root.join("note.md");
+        fs::write(&file_path, "Body").unwrap();
+        fs::write(
+            project_root.join(".export.toml"),
+            "version = 1\n\n[publish]\nflatten = \"yes\"\n",
+        )
+        .unwrap();
+
+        let response = publish_project(PublishRequest {
+            project_root: project_root.to_string_lossy().to_string(),
+            files: vec![file_path.to_string_lossy().to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(!response.ok);
+        let error = response.error.unwrap();
+        assert!(matches!(error.code, PublishErrorCode::ConfigInvalid));
+        assert!(error.detail.unwrap().contains("Invalid"));
+        assert!(!project_root.join("_publish").exists());
+
+        let _ = fs::remove_dir_all(&project_root);
+    }
+
+    #[test]
+    fn publish_project_copies_non_utf8_files_unchanged() {
+        let project_root = temp_dir("publish-latin1");
+        let file_path = project_root.join("note.md");
+        let bytes = b"Caf\xe9 ![a](/img/a.png)\n";
+        fs::write(&file_path, bytes).unwrap();
+
+        let response = publish_project(PublishRequest {
+            project_root: project_root.to_string_lossy().to_string(),
+            files: vec![file_path.to_string_lossy().to_string()],
+            base_path: Some("/docs".to_string()),
+            ..Default::default()
+        })
+        .expect("publish should succeed");
+
+        assert!(response.ok);
+        assert_eq!(
+            fs::read(project_root.join("_publish/note.md")).unwrap(),
+            bytes
+        );
+        assert!(response
+            .warnings
+            .iter()
+            .any(|warning| warning.contains("not valid UTF-8")));
+
+        let _ = fs::remove_dir_all(&project_root);
+    }
+
+    #[test]
+    fn publish_project_renders_markdown_to_html() {
+        let project_root = temp_dir("publish-render");
+        let file_path = project_root.join("note.md");
+        fs::write(
+            &file_path,
+            "---\ntitle: Fish & Chips\n---\n# Hello\n\nSee [intro](intro.md#setup) and [site](https://example.com/a.md).\n",
+        )
+        .unwrap();
+        fs::write(
+            project_root.join(".export.toml"),
+            "version = 1\n\n[publish]\noutput_dir = \"_site\"\n",
+        )
+        .unwrap();
+
+        let response = publish_project(PublishRequest {
+            project_root: project_root.to_string_lossy().to_string(),
+            files: vec![file_path.to_string_lossy().to_string()],
+            render_html: true,
+            html_only: true,
+            ..Default::default()
+        })
+        .expect("publish should succeed");
+
+        assert!(response.ok);
+        let html = fs::read_to_string(project_root.join("_site/note.html")).unwrap();
+        assert!(html.contains("<title>Fish &amp; Chips</title>"));
+        assert!(html.contains("<h1>Hello</h1>"));
+        assert!(html.contains("href=\"intro.html#setup\""));
+        assert!(html.contains("href=\"https://example.com/a.md\""));
+        assert!(!html.contains("title: Fish"));
+        assert!(!project_root.join("_site/note.md").exists());
+
+        let _ = fs::remove_dir_all(&project_root);
+    }
+
+    #[test]
+    fn publish_project_adds_integrity_to_rendered_html() {
+        let project_root = temp_dir("publish-integrity");
+        fs::write(project_root.join("app.js"), "console.log(1);\n").unwrap();
+        fs::write(
+            project_root.join("note.md"),
+            "<script src=\"app.js\"></script>\n\n# Hello\n\n[Source](app.js)\n",
+        )
+        .unwrap();
+        fs::write(project_root.join("plain.md"), "# Plain\n").unwrap();
+        let request = |file: &str| PublishRequest {
+            project_root: project_root.to_string_lossy().to_string(),
+            files: vec![project_root.join(file).to_string_lossy().to_string()],
+            render_html: true,
+            integrity: true,
+            ..Default::default()
+        };
+
+        let response = publish_project(request("plain.md")).expect("publish should succeed");
+        assert!(response.ok);
+        assert!(!project_root.join("_publish/integrity.json").exists());
+
+        let response = publish_project(request("note.md")).expect("publish should succeed");
+        assert!(response.ok);
+        let html = fs::read_to_string(project_root.join("_publish/note.html")).unwrap();
+        assert!(html.contains("integrity=\"sha384-"), "{}", html);
+        let integrity = fs::read_to_string(project_root.join("_publish/integrity.json")).unwrap();
+        assert!(integrity.contains("\"app.js\""), "{}", integrity);
+
+        let _ = fs::remove_dir_all(&project_root);
+    }
+
+    #[test]
+    fn publish_project_writes_control_files() {
+        let project_root = temp_dir("publish-control-files");
+        let file_path = project_root.join("note.md");
+        fs::write(&file_path, "Body").unwrap();
+        fs::create_dir_all(project_root.join("_site")).unwrap();
+        fs::write(project_root.join("_site/CNAME"), "old.example.com\n").unwrap();
+        fs::write(
+            project_root.join(".export.toml"),
+            "version = 1\n\n[publish]\noutput_dir = \"_site\"\n",
+        )
+        .unwrap();
+
+        let response = publish_project(PublishRequest {
+            project_root: project_root.to_string_lossy().to_string(),
+            files: vec![file_path.to_string_lossy().to_string()],
+            nojekyll: true,
+            cname: Some("https://docs.example.com/".to_string()),
+            ..Default::default()
+        })
+        .expect("publish should succeed");
+
+        assert!(response.ok);
+        assert_eq!(
+            response.summary,
+            "Published 1 file(s), 0 asset(s) and 2 control file(s)"
+        );
+        assert!(project_root.join("_site/.nojekyll").is_file());
+        assert_eq!(
+            fs::read_to_string(project_root.join("_site/CNAME")).unwrap(),
+            "docs.example.com\n"
+        );
+
+        let _ = fs::remove_dir_all(&project_root);
+    }
+
+    #[test]
+    fn publish_project_fails_without_files() {
+        let project_root = temp_dir("publish-empty");
+        let result = publish_project(PublishRequest {
+            project_root: project_root.to_string_lossy().to_string(),
+            files: vec![],
+            ..Default::default()
+        })
+        .expect("publish should return a structured failure");
+
+        assert!(!result.ok);
+        assert!(matches!(
+            result.error.map(|error| error.code),
+            Some(PublishErrorCode::NoFilesSelected)
+        ));
+        let _ = fs::remove_dir_all(&project_root);
+    }
+
+    #[test]
+    fn rewrite_base_path_prefixes_root_absolute_links() {
+        let content = "![a](/img/a.png) [b](//cdn.example.com/b) [c](https://x.y/c) [d](</guide.md>) [e](rel.md) [f](/docs/f.md)";
+        let (rewritten, count) = rewrite_base_path(content, "/docs");
+        assert_eq!(count, 3);
+        assert_eq!(
+            rewritten,
+            "![a](/docs/img/a.png) [b](//cdn.example.com/b) [c](https://x.y/c) [d](</docs/guide.md>) [e](rel.md) [f](/docs/docs/f.md)"
+        );
+    }
+
+    #[test]
+    fn publish_project_keeps_and_restores_backup() {
+        let project_root = temp_dir("publish-backup");
+        let file_path = project_root.join("note.md");
+        let request = |keep_backup| PublishRequest {
+            project_root: project_root.to_string_lossy().to_string(),
+            files: vec![file_path.to_string_lossy().to_string()],
+            keep_backup,
+            ..Default::default()
+        };
+
+        fs::write(&file_path, "first").unwrap();
+        publish_project(request(false)).expect("first publish should succeed");
+        fs::write(&file_path, "second").unwrap();
+        publish_project(request(true)).expect("second publish should succeed");
+
+        let backup = project_root.join("_publish.bak/note.md");
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "first");
+
+        restore_publish_backup(PublishBackupRequest {
+            project_root: project_root.to_string_lossy().to_string(),
+            output_dir: None,
+        })
+        .expect("restore should succeed");
+        let live = project_root.join("_publish/note.md");
+        assert_eq!(fs::read_to_string(&live).unwrap(), "first");
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "second");
 
-    let status = run_git_command(&output_dir_canon, &mut logs, &["status", "--porcelain"])?;
-    if status.trim().is_empty() {
-        append_log(
-            &output_dir_canon.join(".deploy.log"),
-            "DEPLOY",
-            "No changes to deploy",
-        )?;
-        return Ok(DeployResponse {
-            ok: true,
-            summary: "No changes to deploy".to_string(),
-            logs,
+        let _ = fs::remove_dir_all(&project_root);
+    }
+
+    #[test]
+    fn restore_publish_backup_keeps_dotted_output_names() {
+        let project_root = temp_dir("publish-backup-dotted");
+        let file_path = project_root.join("note.md");
+        let request = |keep_backup| PublishRequest {
+            project_root: project_root.to_string_lossy().to_string(),
+            files: vec![file_path.to_string_lossy().to_string()],
+            output_dir: Some("site.v2".to_string()),
+            keep_backup,
+            ..Default::default()
+        };
+        fs::create_dir_all(project_root.join("site.swap")).unwrap();
+        fs::write(project_root.join("site.swap/keep.txt"), "unrelated").unwrap();
+
+        fs::write(&file_path, "first").unwrap();
+        publish_project(request(false)).expect("first publish should succeed");
+        fs::write(&file_path, "second").unwrap();
+        publish_project(request(true)).expect("second publish should succeed");
+
+        restore_publish_backup(PublishBackupRequest {
+            project_root: project_root.to_string_lossy().to_string(),
+            output_dir: Some("site.v2".to_string()),
+        })
+        .expect("restore should succeed");
+        assert_eq!(
+            fs::read_to_string(project_root.join("site.v2/note.md")).unwrap(),
+            "first"
+        );
+        assert_eq!(
+            fs::read_to_string(project_root.join("site.v2.bak/note.md")).unwrap(),
+            "second"
+        );
+        assert_eq!(
+            fs::read_to_string(project_root.join("site.swap/keep.txt")).unwrap(),
+            "unrelated"
+        );
+        assert!(!project_root.join("site.v2.swap").exists());
+
+        let _ = fs::remove_dir_all(&project_root);
+    }
+
+    #[test]
+    fn copy_errors_name_the_path_that_failed() {
+        let project_root = temp_dir("copy-errors");
+        let source = project_root.join("gone.png");
+        let target = project_root.join("_publish/img/gone.png");
+        fs::create_dir_all(target.parent().unwrap()).unwrap();
+
+        let error = fs::copy(&source, &target).unwrap_err();
+        assert_eq!(
+            transfer_error(&source, &target, error),
+            format!("File does not exist: {}", source.display())
+        );
+
+        fs::write(&source, "png").unwrap();
+        let target = project_root.join("_missing/gone.png");
+        let error = fs::copy(&source, &target).unwrap_err();
+        assert_eq!(
+            transfer_error(&source, &target, error),
+            format!(
+                "Directory does not exist: {}",
+                project_root.join("_missing").display()
+            )
+        );
+
+        let _ = fs::remove_dir_all(&project_root);
+    }
+
+    #[test]
+    fn find_orphan_assets_skips_referenced_and_ignored() {
+        let project_root = temp_dir("orphans");
+        fs::create_dir_all(project_root.join("img")).unwrap();
+        fs::create_dir_all(project_root.join("_publish/img")).unwrap();
+        fs::write(project_root.join("post.md"), "![a](img/used.png?v=2)\n").unwrap();
+        fs::write(project_root.join("img/used.png"), "png").unwrap();
+        fs::write(project_root.join("img/unused.jpg"), "jpg").unwrap();
+        fs::write(project_root.join("img/raw.psd.png"), "png").unwrap();
+        fs::write(project_root.join("_publish/img/copy.png"), "png").unwrap();
+        fs::write(project_root.join(".exportignore"), "*.psd.png\n").unwrap();
+
+        let orphans = find_orphan_assets(project_root.to_string_lossy().to_string()).unwrap();
+        assert_eq!(orphans, vec!["img/unused.jpg"]);
+
+        let _ = fs::remove_dir_all(&project_root);
+    }
+
+    #[test]
+    fn inject_integrity_hashes_local_scripts_and_styles() {
+        let html = "<link rel=\"stylesheet\" href=\"/css/site.css\">\n<script src='app.js?v=1'></script>\n<script src=\"https://cdn.example.com/x.js\"></script>\n<link rel=stylesheet href=missing.css />";
+        let (output, report) = inject_integrity(html, |url| match url {
+            "/css/site.css" => Some("sha384-css".to_string()),
+            "app.js" => Some("sha384-js".to_string()),
+            _ => None,
         });
+        assert_eq!(report.hashed, 2);
+        assert_eq!(report.missing, vec!["missing.css"]);
+        assert!(output.contains(
+            "href=\"/css/site.css\" integrity=\"sha384-css\" crossorigin=\"anonymous\">"
+        ));
+        assert!(
+            output.contains("src='app.js?v=1' integrity=\"sha384-js\" crossorigin=\"anonymous\">")
+        );
+        assert!(output.contains("<script src=\"https://cdn.example.com/x.js\"></script>"));
     }
 
-    let message = format!(
-        "Publish snapshot @ {}",
-        Local::now().format("%Y-%m-%d %H:%M:%S")
-    );
-    run_git_command(
-        &output_dir_canon,
-        &mut logs,
-        &["commit", "-m", message.as_str()],
-    )?;
+    #[test]
+    fn render_deploy_message_fills_tokens() {
+        let status = [
+            StatusEntry {
+                code: "A ".to_string(),
+                path: "posts/new.md".to_string(),
+            },
+            StatusEntry {
+                code: " M".to_string(),
+                path: "index.md".to_string(),
+            },
+        ];
+        assert_eq!(
+            render_deploy_message(
+                "Deploy {count} file(s) on {date}: {files}",
+                &status,
+                "2024-05-01"
+            ),
+            "Deploy 2 file(s) on 2024-05-01: posts/new.md, index.md"
+        );
+    }
 
-    run_git_command(
-        &output_dir_canon,
-        &mut logs,
-        &["push", "-u", remote_name.as_str(), branch.as_str()],
-    )?;
+    #[test]
+    fn custom_deploy_message_places_the_date() {
+        assert_eq!(
+            custom_deploy_message("Release v1.2", "2024-05-01"),
+            "Release v1.2 @ 2024-05-01"
+        );
+        assert_eq!(
+            custom_deploy_message("Release v1.2 ({date})", "2024-05-01"),
+            "Release v1.2 (2024-05-01)"
+        );
+    }
 
-    append_log(
-        &output_dir_canon.join(".deploy.log"),
-        "DEPLOY",
-        format!("Pushed to {} ({})", remote_name, branch).as_str(),
-    )?;
+    #[test]
+    fn deploy_tag_name_adds_suffix_for_existing_tags() {
+        let dir = temp_dir("deploy-tag");
+        let repo = git::init(&dir).unwrap();
+        fs::write(dir.join("index.html"), "hi").unwrap();
+        git::add_all(&repo).unwrap();
+        git::commit(
+            &repo,
+            "Snapshot",
+            &[],
+            Some("Ernest"),
+            Some("e@example.com"),
+        )
+        .unwrap();
 
-    Ok(DeployResponse {
-        ok: true,
-        summary: format!("Deployed to {} ({})", remote_name, branch),
-        logs,
-    })
-}
+        assert_eq!(
+            deploy_tag_name(&repo, "deploy-{date}", "2024-05-01", false).unwrap(),
+            "deploy-2024-05-01"
+        );
+        git::tag(&repo, "deploy-2024-05-01", false).unwrap();
+        git::tag(&repo, "deploy-2024-05-01-2", false).unwrap();
+        assert_eq!(
+            deploy_tag_name(&repo, "deploy-{date}", "2024-05-01", false).unwrap(),
+            "deploy-2024-05-01-3"
+        );
+        assert!(deploy_tag_name(&repo, "deploy-{date}", "2024-05-01", true).is_err());
+        assert!(deploy_tag_name(&repo, "bad tag", "2024-05-01", false).is_err());
 
-fn resolve_output_dir(project_root: &Path, output_dir: Option<&str>) -> Result<PathBuf, String> {
-    let value = output_dir.unwrap_or("_publish").trim();
-    if value.is_empty() {
-        return Err("Publish directory cannot be empty".to_string());
+        let _ = fs::remove_dir_all(&dir);
     }
-    let path = PathBuf::from(value);
-    if path.is_absolute() {
-        Ok(path)
-    } else {
-        Ok(project_root.join(path))
+
+    fn dry_run_site(name: &str) -> PathBuf {
+        let project_root = temp_dir(name);
+        fs::create_dir_all(project_root.join("_site")).unwrap();
+        fs::write(project_root.join("_site/index.html"), "hi").unwrap();
+        project_root
     }
-}
 
-fn resolve_remote(
-    repo_path: &Path,
-    remote: &str,
-    logs: &mut Vec<String>,
-) -> Result<(String, String), String> {
-    let trimmed = remote.trim();
-    let looks_like_url = trimmed.contains("://") || trimmed.starts_with("git@");
-    let remote_name = if looks_like_url {
-        "origin".to_string()
-    } else {
-        trimmed.to_string()
-    };
+    fn dry_run_deploy(project_root: &Path, remotes: Vec<String>) -> DeployResponse {
+        run_deploy_with_agent(
+            DeployRequest {
+                project_root: project_root.to_string_lossy().to_string(),
+                output_dir: Some("_site".to_string()),
+                remote: "git@example.com:me/site.git".to_string(),
+                remotes,
+                dry_run: true,
+                ..Default::default()
+            },
+            &AtomicBool::new(false),
+            &|_| {},
+            &|| true,
+        )
+        .expect("dry run should succeed")
+    }
 
-    if looks_like_url {
-        let existing = Command::new("git")
-            .args(["remote", "get-url", &remote_name])
-            .current_dir(repo_path)
-            .output();
-        if existing.is_err() || !existing.as_ref().unwrap().status.success() {
-            let _ = run_git_command(repo_path, logs, &["remote", "add", &remote_name, trimmed]);
-        } else {
-            let _ = run_git_command(
-                repo_path,
-                logs,
-                &["remote", "set-url", &remote_name, trimmed],
-            );
+    #[test]
+    fn deploy_dry_run_reports_pending_changes_without_committing() {
+        let project_root = dry_run_site("deploy-dry-run");
+
+        let response = dry_run_deploy(&project_root, Vec::new());
+
+        assert!(response.ok);
+        assert_eq!(
+            response.summary,
+            "Dry run: 1 change(s) would be deployed to origin (main)"
+        );
+        assert!(response.logs.contains(&"A  index.html".to_string()));
+        assert!(!project_root.join("_site/.git").exists());
+
+        let _ = fs::remove_dir_all(&project_root);
+    }
+
+    #[test]
+    fn deploy_dry_run_lists_every_remote() {
+        let project_root = dry_run_site("deploy-mirrors");
+
+        let response = dry_run_deploy(
+            &project_root,
+            vec![
+                "git@example.com:me/site.git".to_string(),
+                "ssh://backup.example.com/site.git".to_string(),
+            ],
+        );
+
+        assert_eq!(
+            response.summary,
+            "Dry run: 1 change(s) would be deployed to origin, mirror-1 (main)"
+        );
+        assert!(response.logs.contains(
+            &"DRY RUN: would run git remote set-url mirror-1 ssh://backup.example.com/site.git"
+                .to_string()
+        ));
+        assert!(!project_root.join("_site/.git").exists());
+
+        let _ = fs::remove_dir_all(&project_root);
+    }
+
+    // A project with a published `_site` repository whose `origin` is an SSH
+    // URL that git pushes into a local bare repository instead.
+    fn deploy_site(name: &str) -> (PathBuf, PathBuf) {
+        let project_root = temp_dir(name);
+        let remote = project_root.join("remote.git");
+        run_git(&project_root, &["init", "-q", "--bare", "remote.git"]);
+        let site = project_root.join("_site");
+        fs::create_dir_all(&site).unwrap();
+        fs::write(site.join("index.html"), "v1").unwrap();
+        run_git(&site, &["init", "-q"]);
+        for (key, value) in [
+            ("user.name", "Ernest"),
+            ("user.email", "ernest@example.com"),
+            ("commit.gpgsign", "false"),
+        ] {
+            run_git(&site, &["config", key, value]);
         }
+        run_git(
+            &site,
+            &[
+                "config",
+                &format!("url.{}.pushInsteadOf", remote.display()),
+                "ssh://example.invalid/site.git",
+            ],
+        );
+        (project_root, remote)
     }
 
-    let url = run_git_command(repo_path, logs, &["remote", "get-url", &remote_name])?;
-    Ok((remote_name, url.trim().to_string()))
-}
+    fn run_git(dir: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {:?}: {:?}", args, output);
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    }
 
-fn is_ssh_url(url: &str) -> bool {
-    url.starts_with("git@") || url.starts_with("ssh://")
-}
+    fn deploy(
+        project_root: &Path,
+        configure: impl FnOnce(&mut DeployRequest),
+    ) -> Result<DeployResponse, String> {
+        let mut request = DeployRequest {
+            project_root: project_root.to_string_lossy().to_string(),
+            output_dir: Some("_site".to_string()),
+            remote: "ssh://example.invalid/site.git".to_string(),
+            ..Default::default()
+        };
+        configure(&mut request);
+        run_deploy_with_agent(request, &AtomicBool::new(false), &|_| {}, &|| true)
+    }
 
-fn run_git_command(
-    repo_path: &Path,
-    logs: &mut Vec<String>,
-    args: &[&str],
-) -> Result<String, String> {
-    let output = Command::new("git")
-        .args(args)
-        .current_dir(repo_path)
-        .output()
-        .map_err(|error| error.to_string())?;
+    #[test]
+    fn deploy_rolls_back_to_the_previously_pushed_commit() {
+        let (project_root, remote) = deploy_site("deploy-rollback");
+        deploy(&project_root, |_| {}).expect("first deploy should succeed");
+        let first = run_git(&remote, &["rev-parse", "main"]);
+        fs::write(project_root.join("_site/index.html"), "v2").unwrap();
+        deploy(&project_root, |_| {}).expect("second deploy should succeed");
+        let second = run_git(&remote, &["rev-parse", "main"]);
+        // An unpushed local commit must not shift what `previous` means.
+        fs::write(project_root.join("_site/index.html"), "v3").unwrap();
+        run_git(&project_root.join("_site"), &["commit", "-qam", "local"]);
 
-    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-    logs.push(format!("git {}", args.join(" ")));
+        let dry_run = deploy(&project_root, |request| {
+            request.rollback_to = Some("previous".to_string());
+            request.dry_run = true;
+        })
+        .unwrap();
+        assert_eq!(
+            dry_run.summary,
+            format!(
+                "Dry run: main would be rolled back to {} on origin",
+                &first[..7]
+            )
+        );
+        assert_eq!(run_git(&remote, &["rev-parse", "main"]), second);
 
-    if output.status.success() {
-        Ok(format!("{}{}", stdout, stderr))
-    } else if stderr.trim().is_empty() {
-        Err(stdout)
-    } else {
-        Err(format!("{}{}", stdout, stderr))
+        let error = deploy(&project_root, |request| {
+            request.rollback_to = Some("no-such-ref".to_string());
+        })
+        .unwrap_err();
+        assert_eq!(error, "Rollback target not found: no-such-ref");
+
+        let response = deploy(&project_root, |request| {
+            request.rollback_to = Some("previous".to_string());
+        })
+        .unwrap();
+        assert_eq!(
+            response.summary,
+            format!("Rolled back main to {} on origin", &first[..7])
+        );
+        assert!(response
+            .logs
+            .contains(&"Force pushing main to origin with --force-with-lease".to_string()));
+        assert_eq!(run_git(&remote, &["rev-parse", "main"]), first);
+
+        let _ = fs::remove_dir_all(&project_root);
     }
-}
 
-fn append_log(path: &Path, label: &str, message: &str) -> Result<(), String> {
-    let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
-    let entry = format!("{} [{}] {}\n", timestamp, label, message);
-    let mut file = fs::OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(path)
-        .map_err(|error| error.to_string())?;
-    file.write_all(entry.as_bytes())
-        .map_err(|error| error.to_string())?;
-    Ok(())
-}
+    #[test]
+    fn forced_deploy_is_refused_when_the_remote_moved_since_the_last_deploy() {
+        let (project_root, remote) = deploy_site("deploy-lease");
+        deploy(&project_root, |_| {}).expect("first deploy should succeed");
 
-fn extract_local_assets(content: &str) -> Vec<String> {
-    let mut results = Vec::new();
-    let mut cursor = 0usize;
-    while let Some(pos) = content[cursor..].find("](") {
-        let start = cursor + pos + 2;
-        if let Some(end) = content[start..].find(')') {
-            let raw = &content[start..start + end];
-            let trimmed = raw.trim();
-            let target = trimmed
-                .trim_matches('<')
-                .trim_matches('>')
-                .split_whitespace()
-                .next()
-                .unwrap_or("")
-                .trim();
-            if !target.is_empty()
-                && !target.starts_with("http://")
-                && !target.starts_with("https://")
-                && !target.starts_with("mailto:")
-                && !target.starts_with("tel:")
-                && !target.starts_with('#')
-            {
-                results.push(target.to_string());
-            }
-            cursor = start + end + 1;
-        } else {
-            break;
-        }
+        let other = project_root.join("other");
+        run_git(
+            &project_root,
+            &[
+                "clone",
+                "-q",
+                "-b",
+                "main",
+                &remote.to_string_lossy(),
+                "other",
+            ],
+        );
+        run_git(&other, &["config", "user.name", "Other"]);
+        run_git(&other, &["config", "user.email", "other@example.com"]);
+        run_git(&other, &["config", "commit.gpgsign", "false"]);
+        fs::write(other.join("index.html"), "theirs").unwrap();
+        run_git(&other, &["commit", "-qam", "theirs"]);
+        run_git(&other, &["push", "-q", "origin", "main"]);
+        let theirs = run_git(&remote, &["rev-parse", "main"]);
+
+        fs::write(project_root.join("_site/index.html"), "ours").unwrap();
+        let error = deploy(&project_root, |request| request.force = true).unwrap_err();
+        assert!(
+            error.contains("stale info") || error.contains("rejected"),
+            "{}",
+            error
+        );
+        assert_eq!(run_git(&remote, &["rev-parse", "main"]), theirs);
+
+        let _ = fs::remove_dir_all(&project_root);
     }
-    results
-}
 
-fn resolve_asset_path(project_root: &Path, file_path: &Path, asset: &str) -> Option<PathBuf> {
-    let trimmed = asset.trim();
-    if trimmed.is_empty() {
-        return None;
+    #[test]
+    fn watched_git_command_streams_stderr_lines() {
+        let dir = temp_dir("git-watched-stream");
+        git::init(&dir).unwrap();
+        let streamed = Mutex::new(Vec::new());
+        let emit = |line: &str| streamed.lock().unwrap().push(line.to_string());
+        let mut logs = DeployLogs::new(&emit);
+
+        let error = run_git_command_watched(
+            &dir,
+            &mut logs,
+            &["rev-parse", "--verify", "missing-ref"],
+            None,
+            Duration::from_secs(10),
+            &AtomicBool::new(false),
+        )
+        .unwrap_err();
+
+        assert!(error.contains("fatal:"), "{}", error);
+        assert_eq!(logs.lines, vec!["git rev-parse --verify missing-ref"]);
+        drop(logs);
+        let streamed = streamed.into_inner().unwrap();
+        assert_eq!(streamed[0], "git rev-parse --verify missing-ref");
+        assert!(streamed[1..].iter().any(|line| line.starts_with("fatal:")));
+
+        let _ = fs::remove_dir_all(&dir);
     }
-    if trimmed.starts_with('/') {
-        return Some(project_root.join(trimmed.trim_start_matches('/')));
+
+    #[test]
+    fn publish_project_honours_publishignore() {
+        let project_root = temp_dir("publishignore");
+        fs::create_dir_all(project_root.join("img")).unwrap();
+        fs::write(
+            project_root.join("note.md"),
+            "![s](img/sprite.svg) ![i](search/index.json) ![a](img/a.png)",
+        )
+        .unwrap();
+        fs::write(project_root.join("img/sprite.svg"), "svg").unwrap();
+        fs::write(project_root.join("img/a.png"), "png").unwrap();
+        fs::write(
+            project_root.join(".publishignore"),
+            "search/**\nsprite.svg\n",
+        )
+        .unwrap();
+
+        let response = publish_project(PublishRequest {
+            project_root: project_root.to_string_lossy().to_string(),
+            files: vec![project_root.join("note.md").to_string_lossy().to_string()],
+            ignore_assets: vec!["!sprite.svg".to_string(), "*.png".to_string()],
+            ..Default::default()
+        })
+        .expect("publish should succeed");
+
+        assert!(response.ok);
+        assert!(response.warnings.is_empty(), "{:?}", response.warnings);
+        assert!(project_root.join("_publish/img/sprite.svg").exists());
+        assert!(!project_root.join("_publish/img/a.png").exists());
+
+        let _ = fs::remove_dir_all(&project_root);
     }
-    let parent = file_path.parent()?;
-    Some(parent.join(trimmed))
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fs;
-    use std::time::{SystemTime, UNIX_EPOCH};
+    #[test]
+    fn publish_project_can_skip_gitignored_paths() {
+        let project_root = temp_dir("gitignore");
+        Repository::init(&project_root).unwrap();
+        fs::create_dir_all(project_root.join("build")).unwrap();
+        fs::write(project_root.join("note.md"), "![c](build/chart.png)").unwrap();
+        fs::write(project_root.join("scratch.md"), "draft").unwrap();
+        fs::write(project_root.join("build/chart.png"), "png").unwrap();
+        fs::write(project_root.join("build/.gitignore"), "*.png\n").unwrap();
+        fs::write(project_root.join(".git/info/exclude"), "scratch.md\n").unwrap();
 
-    fn temp_dir(name: &str) -> PathBuf {
-        let suffix = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_nanos();
-        let dir = std::env::temp_dir().join(format!("ernest-test-{}-{}", name, suffix));
-        fs::create_dir_all(&dir).unwrap();
-        dir
+        let response = publish_project(PublishRequest {
+            project_root: project_root.to_string_lossy().to_string(),
+            files: vec![
+                project_root.join("note.md").to_string_lossy().to_string(),
+                project_root
+                    .join("scratch.md")
+                    .to_string_lossy()
+                    .to_string(),
+            ],
+            respect_gitignore: true,
+            ..Default::default()
+        })
+        .expect("publish should succeed");
+
+        assert!(response.ok);
+        assert_eq!(
+            response.warnings,
+            vec!["Skipped gitignored paths: build/chart.png, scratch.md".to_string()]
+        );
+        assert!(project_root.join("_publish/note.md").exists());
+        assert!(!project_root.join("_publish/scratch.md").exists());
+        assert!(!project_root.join("_publish/build/chart.png").exists());
+
+        let _ = fs::remove_dir_all(&project_root);
     }
 
     #[test]
-    fn resolve_output_dir_joins_relative() {
-        let root = PathBuf::from("/tmp/project-root");
-        let result = resolve_output_dir(&root, Some("_publish")).unwrap();
-        assert_eq!(result, root.join("_publish"));
+    fn publish_project_copies_frontmatter_assets() {
+        let project_root = temp_dir("frontmatter-assets");
+        fs::create_dir_all(project_root.join("img")).unwrap();
+        fs::write(
+            project_root.join("note.md"),
+            "---\ncover: img/cover.png\nimage:\n  - img/og.png\n  - https://cdn.example.com/x.png\n---\nBody\n",
+        )
+        .unwrap();
+        fs::write(project_root.join("img/cover.png"), "png").unwrap();
+        fs::write(project_root.join("img/og.png"), "png").unwrap();
+
+        let response = publish_project(PublishRequest {
+            project_root: project_root.to_string_lossy().to_string(),
+            files: vec![project_root.join("note.md").to_string_lossy().to_string()],
+            ..Default::default()
+        })
+        .expect("publish should succeed");
+
+        assert!(response.ok);
+        assert!(response.warnings.is_empty(), "{:?}", response.warnings);
+        assert!(project_root.join("_publish/img/cover.png").exists());
+        assert!(project_root.join("_publish/img/og.png").exists());
+
+        let _ = fs::remove_dir_all(&project_root);
     }
 
     #[test]
-    fn publish_project_copies_files() {
-        let project_root = temp_dir("publish");
-        let file_path = project_root.join("note.md");
-        fs::write(&file_path, "---\ntitle: Hello\n---\nBody").unwrap();
+    fn publish_project_writes_manifest_json() {
+        let project_root = temp_dir("manifest");
+        fs::create_dir_all(project_root.join("img")).unwrap();
+        fs::write(project_root.join("note.md"), "![c](img/cover.png)\n").unwrap();
+        fs::write(project_root.join("img/cover.png"), "png").unwrap();
 
         let response = publish_project(PublishRequest {
             project_root: project_root.to_string_lossy().to_string(),
-            files: vec![file_path.to_string_lossy().to_string()],
-            output_dir: Some("_publish".into()),
+            files: vec![project_root.join("note.md").to_string_lossy().to_string()],
+            ..Default::default()
         })
         .expect("publish should succeed");
 
         assert!(response.ok);
-        assert!(response.summary.contains("Published"));
-        let published = project_root.join("_publish/note.md");
-        assert!(published.exists(), "expected published file to exist");
+        let manifest_path = PathBuf::from(response.manifest_path.expect("manifest path"));
+        assert_eq!(
+            manifest_path,
+            project_root
+                .canonicalize()
+                .unwrap()
+                .join("_publish/manifest.json")
+        );
+
+        let manifest: PublishManifest =
+            serde_json::from_str(&fs::read_to_string(&manifest_path).unwrap()).unwrap();
+        assert_eq!((manifest.file_count, manifest.asset_count), (1, 1));
+        let cover = manifest
+            .entries
+            .iter()
+            .find(|entry| entry.path == "img/cover.png")
+            .expect("cover entry");
+        assert_eq!((cover.kind.as_str(), cover.size), ("asset", 3));
+        assert_eq!(cover.sha256, sha256_hex("png".as_bytes()).unwrap());
 
         let _ = fs::remove_dir_all(&project_root);
     }
 
     #[test]
-    fn publish_project_fails_without_files() {
-        let project_root = temp_dir("publish-empty");
-        let result = publish_project(PublishRequest {
+    fn publish_project_keeps_a_site_manifest_json() {
+        let project_root = temp_dir("manifest-site");
+        fs::write(project_root.join("note.md"), "Body").unwrap();
+        fs::write(project_root.join("manifest.json"), "{\"name\":\"site\"}").unwrap();
+        let request = || PublishRequest {
             project_root: project_root.to_string_lossy().to_string(),
-            files: vec![],
-            output_dir: None,
-        });
+            files: vec![
+                project_root.join("note.md").to_string_lossy().to_string(),
+                project_root
+                    .join("manifest.json")
+                    .to_string_lossy()
+                    .to_string(),
+            ],
+            ..Default::default()
+        };
+
+        let response = publish_project(request()).unwrap();
+        assert!(response.ok);
+        assert!(response.manifest_path.is_none());
+        assert!(response
+            .warnings
+            .iter()
+            .any(|warning| warning.contains("set [publish] manifest_name")));
+        assert_eq!(
+            fs::read_to_string(project_root.join("_publish/manifest.json")).unwrap(),
+            "{\"name\":\"site\"}"
+        );
+
+        fs::write(
+            project_root.join(".export.toml"),
+            "version = 2\n\n[publish]\nmanifest_name = \"publish-manifest.json\"\n",
+        )
+        .unwrap();
+        let response = publish_project(request()).unwrap();
+        let manifest_path = PathBuf::from(response.manifest_path.expect("manifest path"));
+        assert_eq!(manifest_path.file_name().unwrap(), "publish-manifest.json");
+        assert_eq!(
+            fs::read_to_string(project_root.join("_publish/manifest.json")).unwrap(),
+            "{\"name\":\"site\"}"
+        );
 
-        assert!(result.is_err());
         let _ = fs::remove_dir_all(&project_root);
     }
+
+    #[test]
+    fn extract_local_assets_reports_lines() {
+        let content = "# Title\n\n![a](img/a.png)\ntext [b](https://x.y) and [c](docs/c.pdf)\n";
+        let assets = extract_local_assets(content);
+        let found: Vec<(&str, usize)> = assets
+            .iter()
+            .map(|asset| (asset.target.as_str(), asset.line))
+            .collect();
+        assert_eq!(found, vec![("img/a.png", 3), ("docs/c.pdf", 4)]);
+    }
+
+    #[test]
+    fn extract_local_assets_reads_reference_definitions() {
+        let content = "See [the logo][logo] and [docs].\n\n[logo]: <img/logo.png> \"Logo\"\n  [docs]: docs/guide.pdf\n[site]: https://example.com\n[^1]: a footnote\n";
+        let found: Vec<(String, usize)> = extract_local_assets(content)
+            .into_iter()
+            .map(|asset| (asset.target, asset.line))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("img/logo.png".to_string(), 3),
+                ("docs/guide.pdf".to_string(), 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn extract_local_assets_reads_html_attributes() {
+        let content = "<img class=\"wide\" src=\"images/foo.png\" alt='x'/>\n\
+            <video data-src=\"skip.mp4\" src='clips/a.mp4'></video>\n\
+            <a href=\"#top\">top</a> <a href=\"docs/b.pdf\">b</a> ![c](c.png)\n\
+            <img src=\"data:image/png;base64,AAAA\"><img src=\"//cdn.example.com/d.png\">\n";
+        let found: Vec<(String, usize)> = extract_local_assets(content)
+            .into_iter()
+            .map(|asset| (asset.target, asset.line))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("images/foo.png".to_string(), 1),
+                ("clips/a.mp4".to_string(), 2),
+                ("docs/b.pdf".to_string(), 3),
+                ("c.png".to_string(), 3),
+            ]
+        );
+    }
 }